@@ -0,0 +1,86 @@
+//! Compares decoding tournament payloads directly from the index table against rebuilding them
+//! with `unflatten` and parsing the result via JSON.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use serde_json::Value;
+use std::hint::black_box;
+use switzerland_power_calc::sendou::schema::ToResponse;
+use switzerland_power_calc::sendou::turbo_stream::{TurboStreamed, flatten, unflatten};
+
+const TOURNAMENT_FIXTURE: &[u8] = include_bytes!("../test-fixtures/register.data.json");
+const ROUTE: &str = "features/tournament/routes/to.$id";
+
+/// The registered tournament fixture, with its teams and matches repeated `copies` times
+fn payload(copies: u64) -> Vec<u8> {
+    let table = serde_json::from_slice::<Vec<Value>>(TOURNAMENT_FIXTURE).unwrap();
+    let mut response = unflatten(&table).unwrap();
+    let data = &mut response[ROUTE]["data"];
+    let mut root = serde_json::from_str::<Value>(data.as_str().unwrap()).unwrap();
+
+    let tournament = &mut root["tournament"];
+    let teams = tournament["ctx"]["teams"].as_array().unwrap().clone();
+    let matches = tournament["data"]["match"].as_array().unwrap().clone();
+    for copy in 1..copies {
+        for mut team in teams.clone() {
+            offset(&mut team["id"], copy * 10);
+            for member in team["members"].as_array_mut().unwrap() {
+                offset(&mut member["userId"], copy * 1000);
+            }
+            tournament["ctx"]["teams"]
+                .as_array_mut()
+                .unwrap()
+                .push(team);
+        }
+        for mut tourney_match in matches.clone() {
+            offset(&mut tourney_match["id"], copy * 100);
+            for opponent in ["opponent1", "opponent2"] {
+                if tourney_match[opponent].is_object() {
+                    offset(&mut tourney_match[opponent]["id"], copy * 10);
+                }
+            }
+            tournament["data"]["match"]
+                .as_array_mut()
+                .unwrap()
+                .push(tourney_match);
+        }
+    }
+
+    *data = Value::String(root.to_string());
+    serde_json::to_vec(&flatten(&response)).unwrap()
+}
+
+fn offset(id: &mut Value, by: u64) {
+    *id = (id.as_u64().unwrap() + by).into();
+}
+
+fn decode(c: &mut Criterion) {
+    for copies in [10, 100] {
+        let payload = payload(copies);
+        let tournament = serde_json::from_slice::<TurboStreamed<ToResponse>>(&payload)
+            .unwrap()
+            .0
+            .to
+            .data
+            .tournament;
+        let mut group = c.benchmark_group(format!(
+            "decode {} teams, {} matches",
+            tournament.context.teams.len(),
+            tournament.data.matches.len()
+        ));
+        group.bench_function("unflatten", |b| {
+            b.iter(|| {
+                let table = serde_json::from_slice::<Vec<Value>>(black_box(&payload)).unwrap();
+                serde_json::from_value::<ToResponse>(unflatten(&table).unwrap()).unwrap()
+            })
+        });
+        group.bench_function("from_table", |b| {
+            b.iter(|| {
+                serde_json::from_slice::<TurboStreamed<ToResponse>>(black_box(&payload)).unwrap()
+            })
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
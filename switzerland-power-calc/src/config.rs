@@ -0,0 +1,122 @@
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use skillratings::glicko2::{Glicko2Config, Glicko2Rating};
+use std::fs;
+use std::path::Path;
+
+/// The rating settings for a season. This is stored in the database, so that every tournament in a
+/// season is rated the same way.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SeasonConfig {
+    /// The Glicko-2 tau constant, which constrains the change in volatility over time
+    pub tau: f64,
+    /// The Glicko-2 convergence tolerance used when calculating volatility
+    pub convergence_tolerance: f64,
+    /// The deviation at or below which a player's power is considered calculated
+    pub maximum_calced_rd: f64,
+    pub rating_mode: RatingMode,
+    /// Rate all of a tournament's sets as a single rating period instead of one set at a time
+    pub tournament_rating_period: bool,
+    pub team_rating: TeamRatingStrategy,
+    pub seeding: SeedingConfig,
+}
+
+/// How the result of a set is turned into Glicko-2 outcomes
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum RatingMode {
+    /// The whole set is a single win or loss
+    #[default]
+    Set,
+    /// Each map of the set is a win or loss within a single rating period, so that a 3–2 counts
+    /// for less than a 3–0. Sets without a map score (such as forfeits) are rated as a whole.
+    Maps,
+}
+
+/// How the members of a team are rated in team events
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum TeamRatingStrategy {
+    /// Each member is rated individually against the average rating of the opposing team
+    #[default]
+    PerMember,
+    /// The team is rated as a single player with the average rating of its members, and the
+    /// change is applied to every member
+    Average,
+}
+
+/// How a new player's starting rating is derived from their sendou.ink seeding skill ordinal
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SeedingConfig {
+    pub min_ordinal: f64,
+    pub max_ordinal: f64,
+    /// The rating given to a player with an ordinal of 0
+    pub base_rating: f64,
+    /// The rating added for each point of ordinal
+    pub rating_per_ordinal: f64,
+    /// The deviation given to a player with an ordinal of 0
+    pub base_deviation: f64,
+    /// The deviation removed for each point of ordinal away from 0
+    pub deviation_per_ordinal: f64,
+}
+
+impl SeasonConfig {
+    pub fn read(file: &Path) -> Result<Self> {
+        Ok(serde_json::from_reader(fs::File::open(file)?)?)
+    }
+
+    pub fn glicko2(&self) -> Glicko2Config {
+        Glicko2Config {
+            tau: self.tau,
+            convergence_tolerance: self.convergence_tolerance,
+        }
+    }
+
+    /// How close a player with `deviation` is to having their Switzerland Power calculated, from 0
+    /// to 1
+    pub fn calc_percentage(&self, deviation: f64) -> f64 {
+        const DEFAULT_RD: f64 = 350.0;
+        1.0 - (deviation - self.maximum_calced_rd) / (DEFAULT_RD - self.maximum_calced_rd)
+    }
+}
+
+impl Default for SeasonConfig {
+    fn default() -> Self {
+        let glicko2 = Glicko2Config::default();
+        Self {
+            tau: glicko2.tau,
+            convergence_tolerance: glicko2.convergence_tolerance,
+            maximum_calced_rd: 170.0,
+            rating_mode: RatingMode::default(),
+            tournament_rating_period: false,
+            team_rating: TeamRatingStrategy::default(),
+            seeding: SeedingConfig::default(),
+        }
+    }
+}
+
+impl SeedingConfig {
+    pub fn starting_rating(&self, seeding_skill_ordinal: f64) -> Glicko2Rating {
+        let ordinal = seeding_skill_ordinal.clamp(self.min_ordinal, self.max_ordinal);
+        Glicko2Rating {
+            rating: ordinal * self.rating_per_ordinal + self.base_rating,
+            deviation: self.base_deviation - ordinal.abs() * self.deviation_per_ordinal,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for SeedingConfig {
+    fn default() -> Self {
+        Self {
+            min_ordinal: -10.0,
+            max_ordinal: 40.0,
+            base_rating: 1500.0,
+            rating_per_ordinal: 10.0,
+            base_deviation: 350.0,
+            deviation_per_ordinal: 3.75,
+        }
+    }
+}
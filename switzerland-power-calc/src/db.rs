@@ -1,12 +1,13 @@
-use crate::MAXIMUM_CALCED_RD;
+use crate::config::SeasonConfig;
 use crate::error::Result;
 use crate::sendou::SendouId;
 use crate::sendou::lang::Language;
+use chrono::{DateTime, Utc};
 use hashlink::LinkedHashMap;
 use itertools::Itertools;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
-use skillratings::glicko2::Glicko2Rating;
+use skillratings::glicko2::{Glicko2Rating, expected_score};
 use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::fs;
@@ -18,6 +19,10 @@ pub type SwitzerlandPlayerMap = LinkedHashMap<PlayerId, SwitzerlandPlayer>;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Database {
     pub players: Vec<SwitzerlandPlayer>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tournaments: Vec<TournamentRecord>,
+    #[serde(default)]
+    pub config: SeasonConfig,
     #[serde(default)]
     version: DbVersion,
 }
@@ -54,6 +59,81 @@ pub struct SwitzerlandPlayer {
     pub rating: Glicko2Rating,
     #[serde(skip)]
     pub unrated: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<RatingHistoryEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RatingHistoryEntry {
+    pub tournament_id: SendouId,
+    pub match_id: SendouId,
+    pub date: DateTime<Utc>,
+    pub old_rating: Glicko2Rating,
+    pub new_rating: Glicko2Rating,
+    #[serde(alias = "opponent", deserialize_with = "one_or_many_players")]
+    pub opponents: Vec<PlayerId>,
+    pub result: SetResult,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SetResult {
+    Win,
+    Loss,
+}
+
+impl SetResult {
+    pub fn inverse(self) -> Self {
+        match self {
+            SetResult::Win => SetResult::Loss,
+            SetResult::Loss => SetResult::Win,
+        }
+    }
+}
+
+/// The raw results of a tournament, from which the ratings can be recomputed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TournamentRecord {
+    pub id: SendouId,
+    pub date: DateTime<Utc>,
+    pub participants: Vec<TournamentParticipant>,
+    /// The sets in the order they were rated
+    pub sets: Vec<SetRecord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TournamentParticipant {
+    pub player: PlayerId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    pub seeding_skill_ordinal: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetRecord {
+    pub match_id: SendouId,
+    #[serde(alias = "player1", deserialize_with = "one_or_many_players")]
+    pub team1: Vec<PlayerId>,
+    #[serde(alias = "player2", deserialize_with = "one_or_many_players")]
+    pub team2: Vec<PlayerId>,
+    /// The result from the perspective of `team1`
+    pub result: SetResult,
+    pub score: (u32, u32),
+}
+
+/// Accepts a single player, as recorded before team events were supported, or a list of players
+fn one_or_many_players<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<PlayerId>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(PlayerId),
+        Many(Vec<PlayerId>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(player) => vec![player],
+        OneOrMany::Many(players) => players,
+    })
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq)]
@@ -86,6 +166,12 @@ impl SwitzerlandPlayer {
         !self.unrated && self.calced
     }
 
+    /// The probability of this player winning a set against `opponent`, accounting for the
+    /// deviations of both players
+    pub fn win_probability(&self, opponent: &SwitzerlandPlayer) -> f64 {
+        expected_score(&self.rating, &opponent.rating).0
+    }
+
     pub fn descending_rating_order_cmp(&self, other: &Self) -> Ordering {
         other.rating.rating.total_cmp(&self.rating.rating)
     }
@@ -109,21 +195,32 @@ impl Default for PlayerId {
     }
 }
 
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Database {
     pub fn new() -> Self {
         Self {
             players: vec![],
+            tournaments: vec![],
+            config: SeasonConfig::default(),
             version: DbVersion::CURRENT,
         }
     }
 
-    pub fn new_from_map(map: SwitzerlandPlayerMap) -> Self {
+    /// Creates a database with the given players, keeping the config and tournaments of this one
+    pub fn with_players(&self, map: SwitzerlandPlayerMap) -> Self {
         let mut result = Self {
             players: map
                 .into_iter()
                 .map(|(_, v)| v)
                 .filter(|x| !x.unrated)
                 .collect(),
+            tournaments: self.tournaments.clone(),
+            config: self.config,
             version: DbVersion::CURRENT,
         };
         result.sort();
@@ -135,7 +232,7 @@ impl Database {
             match self.version {
                 DbVersion::Old => {
                     for player in &mut self.players {
-                        if player.rating.deviation <= MAXIMUM_CALCED_RD {
+                        if player.rating.deviation <= self.config.maximum_calced_rd {
                             player.calced = true;
                         }
                     }
@@ -146,6 +243,12 @@ impl Database {
         }
     }
 
+    /// Adds the record of a tournament, replacing any previous record of the same tournament
+    pub fn add_tournament(&mut self, record: TournamentRecord) {
+        self.tournaments.retain(|x| x.id != record.id);
+        self.tournaments.push(record);
+    }
+
     pub fn sort(&mut self) {
         self.players
             .sort_by(SwitzerlandPlayer::descending_rating_order_cmp);
@@ -234,8 +337,10 @@ impl Database {
     }
 }
 
-pub fn init_db(file: &Path) -> Result<()> {
-    Database::new().write(file)?;
+pub fn init_db(file: &Path, config: SeasonConfig) -> Result<()> {
+    let mut db = Database::new();
+    db.config = config;
+    db.write(file)?;
     Ok(())
 }
 
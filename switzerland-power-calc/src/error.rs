@@ -1,6 +1,7 @@
 use std::backtrace::Backtrace;
 use std::fmt::{Debug, Display, Formatter};
 use std::io;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -23,6 +24,8 @@ pub enum ErrorKind {
     Http(#[from] reqwest::Error),
     #[error("URL parsing error: {0}")]
     Url(#[from] url::ParseError),
+    #[error("Rate limited by sendou.ink, retry after {}s", .0.as_secs())]
+    RateLimited(Duration),
     #[error("Discord error: {0}")]
     Discord(#[source] Box<serenity::Error>),
     #[error("Animation error: {0}")]
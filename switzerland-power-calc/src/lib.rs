@@ -0,0 +1,112 @@
+pub mod config;
+pub mod counts;
+pub mod db;
+pub mod error;
+pub mod migration;
+pub mod rating;
+pub mod sendou;
+pub mod simulate;
+
+use crate::db::{SwitzerlandPlayer, SwitzerlandPlayerMap};
+pub use error::{Error, Result};
+use skillratings::glicko2::Glicko2Rating;
+use std::cmp::Ordering;
+
+pub fn summarize_differences(
+    old_results: &SwitzerlandPlayerMap,
+    new_results: &Vec<SwitzerlandPlayer>,
+) {
+    for new_player in new_results {
+        let old_result = old_results.get(&new_player.id);
+        if let Some(old_result) = old_result
+            && old_result.rating == new_player.rating
+        {
+            continue;
+        }
+        print_player_simply(old_result, new_player, true, true);
+    }
+}
+
+pub fn print_player_simply(
+    old_player: Option<&SwitzerlandPlayer>,
+    new_player: &SwitzerlandPlayer,
+    show_rank: bool,
+    show_rd: bool,
+) {
+    println!(
+        "{}",
+        format_player_simply(old_player, new_player, show_rank, show_rd)
+    );
+}
+
+pub fn format_player_simply(
+    old_player: Option<&SwitzerlandPlayer>,
+    new_player: &SwitzerlandPlayer,
+    show_rank: bool,
+    show_rd: bool,
+) -> String {
+    format!(
+        "- {}: {}",
+        new_player.display_name(),
+        format_player_rank_summary(old_player, new_player, show_rank, show_rd)
+    )
+}
+
+pub fn format_player_rank_summary(
+    old_player: Option<&SwitzerlandPlayer>,
+    new_player: &SwitzerlandPlayer,
+    show_rank: bool,
+    show_rd: bool,
+) -> String {
+    if let Some(old_player) = old_player.filter(|p| show_rd || p.calced) {
+        format!(
+            "{} → {} ({:+.1}){}",
+            format_sp(old_player.rating, show_rd),
+            format_sp(new_player.rating, show_rd),
+            new_player.rating.rating - old_player.rating.rating,
+            if show_rank {
+                match (old_player.rank, new_player.rank) {
+                    (Some(old_rank), Some(new_rank)) => {
+                        format!(
+                            "; {}",
+                            match new_rank.cmp(&old_rank) {
+                                Ordering::Equal => format!("#{new_rank} ⇒"),
+                                Ordering::Less => format!("#{old_rank} → #{new_rank} ⇑"),
+                                Ordering::Greater => format!("#{old_rank} → #{new_rank} ⇓"),
+                            }
+                        )
+                    }
+                    (None, Some(new_rank)) => format!("; #{}", new_rank.get()),
+                    (_, None) => "".to_string(),
+                }
+            } else {
+                "".to_string()
+            }
+        )
+    } else {
+        format!(
+            "{}{}",
+            format_sp(new_player.rating, show_rd),
+            if show_rank && let Some(rank) = new_player.rank {
+                format!("; #{}", rank)
+            } else {
+                "".to_string()
+            }
+        )
+    }
+}
+
+pub fn format_sp(rating: Glicko2Rating, show_rd: bool) -> String {
+    let scaled_power = (rating.rating * 10.0).floor().abs() as u32;
+    format!(
+        "{}{}.{} SP{}",
+        if rating.rating < 0.0 { "-" } else { "" },
+        scaled_power / 10,
+        scaled_power % 10,
+        if show_rd {
+            format!(" (RD {})", rating.deviation as i64)
+        } else {
+            "".to_string()
+        },
+    )
+}
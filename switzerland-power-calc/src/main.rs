@@ -1,28 +1,29 @@
-mod counts;
-mod db;
-mod error;
-mod migration;
-mod sendou;
-
-use crate::db::{Database, SwitzerlandPlayer, SwitzerlandPlayerMap};
-use crate::migration::MigrationStyle;
-use crate::sendou::leaderboard::generate_leaderboard_messages;
-use crate::sendou::{SendouId, migration_cli, sendou_cli};
 use clap::Parser;
-use error::{Error, Result};
 use hashlink::LinkedHashMap;
 use itertools::Itertools;
 use skillratings::glicko2::Glicko2Rating;
 use std::backtrace::BacktraceStatus;
-use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fs;
+use std::net::SocketAddr;
 use std::num::{NonZeroU32, NonZeroUsize};
 use std::path::PathBuf;
 use std::process::exit;
 use switzerland_power_animated::{
     AnimationGenerator, AnimationLanguage, MatchOutcome, PowerStatus,
 };
+use switzerland_power_calc::config::SeasonConfig;
+use switzerland_power_calc::db::{self, Database, SetResult, SwitzerlandPlayer};
+use switzerland_power_calc::migration::MigrationStyle;
+use switzerland_power_calc::sendou::leaderboard::generate_leaderboard_messages;
+use switzerland_power_calc::sendou::{
+    SendouId, SinkOptions, bot_cli, migration_cli, replay_cli, sendou_cli,
+};
+use switzerland_power_calc::simulate::simulate_cli;
+use switzerland_power_calc::{
+    Result, format_player_rank_summary, format_sp, print_player_simply, rating,
+    summarize_differences,
+};
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::filter::LevelFilter;
 
@@ -38,6 +39,19 @@ enum Commands {
     Init {
         /// The path to the database file to create
         db: PathBuf,
+        /// A season config JSON file with the rating settings to use. If not specified, the
+        /// default settings are used.
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+    },
+    /// Show or change the rating settings of the season stored in the database
+    Config {
+        /// The path to the database
+        db: PathBuf,
+        /// A season config JSON file to store in the database. Players aren't rerated with the new
+        /// settings until the database is rebuilt.
+        #[arg(short, long)]
+        set: Option<PathBuf>,
     },
     /// Query the database
     Query {
@@ -49,6 +63,29 @@ enum Commands {
         #[arg(short, long)]
         verbose: bool,
     },
+    /// Show how a player's Switzerland Power changed over each tournament
+    History {
+        /// The path to the database
+        db: PathBuf,
+        /// The player to show the history of
+        player: String,
+        /// Output the deviation along with the powers
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Predict the outcome of a set between two players
+    Predict {
+        /// The path to the database
+        db: PathBuf,
+        /// The first player
+        player1: String,
+        /// The second player
+        player2: String,
+        /// How many maps the set is a best of. With seasons rated by maps, the change in rating
+        /// is shown for each score the set could end with.
+        #[arg(short, long, default_value_t = 3)]
+        best_of: u32,
+    },
     /// Summarizes the differences between databases
     Compare {
         /// The path to the old database
@@ -66,6 +103,103 @@ enum Commands {
         out_db: PathBuf,
         /// The URL to the tournament on sendou.ink
         tournament_id: SendouId,
+        /// A directory to save every distinct tournament snapshot and match result fetched from
+        /// sendou.ink to, for auditing or replaying later
+        #[arg(short, long)]
+        record: Option<PathBuf>,
+        /// A directory to save tournaments that sendou.ink sends but can't be parsed to. Instead
+        /// of stopping, the tournament keeps being processed using the last one that could be.
+        #[arg(long)]
+        diagnostics: Option<PathBuf>,
+        /// Where to save the progress of the run, so that it can pick up where it left off if it's
+        /// restarted. Defaults to the path of the output database with the extension
+        /// `.state.json`. Deleted once the run has finished.
+        #[arg(long)]
+        state: Option<PathBuf>,
+        /// Process the tournament without Discord, only logging results to the console. The
+        /// Discord environment variables aren't needed.
+        #[arg(long)]
+        headless: bool,
+        /// A directory to write each player's progress message and animation to, along with a
+        /// `manifest.json` listing them, for stream overlays and websites
+        #[arg(long)]
+        notify_dir: Option<PathBuf>,
+        /// A URL to POST each player's progress message and animation to as multipart form data
+        #[arg(long)]
+        webhook: Option<String>,
+        /// An address, such as 127.0.0.1:8080, to serve a stream overlay on. It serves each
+        /// player's progress and animation, and the live leaderboard, over HTTP and WebSocket.
+        #[arg(long)]
+        overlay: Option<SocketAddr>,
+    },
+    /// Run the Discord bot until stopped with Ctrl+C. Between tournaments, it answers player
+    /// commands and keeps the leaderboard in sync with the database. Moderators can have it
+    /// process a tournament with the /process command.
+    Bot {
+        /// The path to the database. It's replaced with the result of each tournament processed,
+        /// with the database from before each tournament kept next to it.
+        db: PathBuf,
+        /// A directory to save every distinct tournament snapshot and match result fetched from
+        /// sendou.ink to, in a subdirectory for each tournament
+        #[arg(short, long)]
+        record: Option<PathBuf>,
+        /// A directory to save tournaments that sendou.ink sends but can't be parsed to
+        #[arg(long)]
+        diagnostics: Option<PathBuf>,
+        /// A directory to write each player's progress message and animation to, along with a
+        /// `manifest.json` listing them, for stream overlays and websites
+        #[arg(long)]
+        notify_dir: Option<PathBuf>,
+        /// A URL to POST each player's progress message and animation to as multipart form data
+        #[arg(long)]
+        webhook: Option<String>,
+        /// An address, such as 127.0.0.1:8080, to serve a stream overlay on. It keeps serving
+        /// between tournaments, showing the notifications of the latest one.
+        #[arg(long)]
+        overlay: Option<SocketAddr>,
+    },
+    /// Process a saved sendou.ink tournament without contacting sendou.ink or Discord
+    Replay {
+        /// The path to the input database
+        in_db: PathBuf,
+        /// The path to the database to create as a result
+        out_db: PathBuf,
+        /// The path to a saved tournament response (register.data), or a directory recorded with
+        /// `sendou --record` to use the latest snapshot from
+        tournament: PathBuf,
+        /// The directory containing saved match responses, named match-<id>.json, for the
+        /// map-by-map results in the animations. Defaults to the directory containing the
+        /// tournament.
+        #[arg(short, long)]
+        matches: Option<PathBuf>,
+        /// A directory to write each player's progress message and animation to, along with a
+        /// `manifest.json` listing them
+        #[arg(long)]
+        notify_dir: Option<PathBuf>,
+    },
+    /// Recompute every rating from scratch from the tournaments recorded in the database. Players
+    /// without any recorded tournaments are dropped.
+    Rebuild {
+        /// The path to the input database
+        in_db: PathBuf,
+        /// The path to the database to create as a result
+        out_db: PathBuf,
+        /// A season config JSON file with the rating settings to rebuild with. If not specified,
+        /// the settings stored in the input database are used.
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+    },
+    /// Compare how alternate rating settings would have rated the recorded tournaments
+    Simulate {
+        /// The path to the database to compare with
+        db: PathBuf,
+        /// A database to take the recorded tournaments from. If not specified, the tournaments
+        /// recorded in the compared database are used.
+        #[arg(short, long)]
+        matches: Option<PathBuf>,
+        /// Season config JSON files with the alternate settings to simulate
+        #[arg(required = true)]
+        configs: Vec<PathBuf>,
     },
     /// Migrate old string IDs to new Sendou-based IDs or to other names
     MigrateNames {
@@ -231,10 +365,23 @@ fn run(args: Args) -> Result<()> {
         )
         .init();
     match args.command {
-        Init { db } => {
-            db::init_db(&db)?;
+        Init { db, config } => {
+            let config = config
+                .map(|x| SeasonConfig::read(&x))
+                .transpose()?
+                .unwrap_or_default();
+            db::init_db(&db, config)?;
             println!("Initialized DB at {}", db.display());
         }
+        Config { db: db_path, set } => {
+            let mut db = Database::read(&db_path)?;
+            if let Some(config) = set {
+                db.config = SeasonConfig::read(&config)?;
+                db.write(&db_path)?;
+                println!("Updated season config of {}", db_path.display());
+            }
+            println!("{}", serde_json::to_string_pretty(&db.config)?);
+        }
         Query { db, query, verbose } => {
             let results = db::query(&db, query.as_ref(), true)?;
             println!("Found {} players:", results.len());
@@ -251,6 +398,39 @@ fn run(args: Args) -> Result<()> {
                 }
             }
         }
+        History {
+            db,
+            player,
+            verbose,
+        } => {
+            let db = Database::read(&db)?;
+            let Some(player) = db.clone().query(Some(&vec![player]), true).pop() else {
+                println!("No players found!");
+                return Ok(());
+            };
+            print_player_history(&db, &player, verbose);
+        }
+        Predict {
+            db,
+            player1,
+            player2,
+            best_of,
+        } => {
+            let mut db = Database::read(&db)?;
+            let mut players = vec![];
+            db.for_each_matching_mut(&vec![player1, player2], true, |db, index| {
+                players.push(db.players[index].clone())
+            });
+            let [player1, player2] = players.as_slice() else {
+                println!("No players found!");
+                return Ok(());
+            };
+            if player1.id == player2.id {
+                println!("Both players matched {}!", player1.display_name());
+                return Ok(());
+            }
+            print_set_prediction(&db.config, player1, player2, best_of);
+        }
         Compare {
             old_db,
             new_db,
@@ -268,7 +448,96 @@ fn run(args: Args) -> Result<()> {
             in_db,
             out_db,
             tournament_id,
-        } => sendou_cli(&in_db, &out_db, tournament_id)?,
+            record,
+            diagnostics,
+            state,
+            headless,
+            notify_dir,
+            webhook,
+            overlay,
+        } => {
+            let state = state.unwrap_or_else(|| out_db.with_extension("state.json"));
+            sendou_cli(
+                &in_db,
+                &out_db,
+                tournament_id,
+                record.as_deref(),
+                diagnostics.as_deref(),
+                &state,
+                headless,
+                SinkOptions {
+                    dir: notify_dir.as_deref(),
+                    webhook_url: webhook.as_deref(),
+                    overlay_addr: overlay,
+                },
+            )?
+        }
+        Bot {
+            db,
+            record,
+            diagnostics,
+            notify_dir,
+            webhook,
+            overlay,
+        } => bot_cli(
+            &db,
+            record.as_deref(),
+            diagnostics.as_deref(),
+            SinkOptions {
+                dir: notify_dir.as_deref(),
+                webhook_url: webhook.as_deref(),
+                overlay_addr: overlay,
+            },
+        )?,
+        Replay {
+            in_db,
+            out_db,
+            tournament,
+            matches,
+            notify_dir,
+        } => replay_cli(
+            &in_db,
+            &out_db,
+            &tournament,
+            matches.as_deref(),
+            notify_dir.as_deref(),
+        )?,
+        Rebuild {
+            in_db,
+            out_db,
+            config,
+        } => {
+            let mut old_db = Database::read(&in_db)?;
+            let old_players = old_db.clone().into_map();
+            if let Some(config) = config {
+                old_db.config = SeasonConfig::read(&config)?;
+            }
+            let mut new_players = rating::rebuild(&old_db.config, &old_db.tournaments);
+            for player in new_players.values_mut() {
+                player.language = old_players.get(&player.id).and_then(|x| x.language);
+            }
+            let new_db = old_db.with_players(new_players);
+            new_db.write(&out_db)?;
+
+            println!(
+                "Rebuilt {} players from {} tournaments:",
+                new_db.players.len(),
+                new_db.tournaments.len()
+            );
+            summarize_differences(&old_players, &new_db.players);
+            let dropped_players = old_players
+                .keys()
+                .filter(|id| !new_db.players.iter().any(|x| &x.id == *id))
+                .count();
+            if dropped_players > 0 {
+                println!("{dropped_players} players without recorded tournaments were dropped");
+            }
+        }
+        Simulate {
+            db,
+            matches,
+            configs,
+        } => simulate_cli(&db, matches.as_deref(), &configs)?,
         MigrateNames {
             style,
             in_db,
@@ -336,103 +605,117 @@ fn run(args: Args) -> Result<()> {
     Ok(())
 }
 
-pub const MAXIMUM_CALCED_RD: f64 = 170.0;
+pub fn print_player_history(db: &Database, player: &SwitzerlandPlayer, show_rd: bool) {
+    println!(
+        "{}: {}",
+        player.display_name(),
+        format_player_rank_summary(None, player, true, show_rd)
+    );
+    if player.history.is_empty() {
+        println!("No history recorded");
+        return;
+    }
+
+    let display_names = db
+        .players
+        .iter()
+        .map(|p| (&p.id, p.display_name()))
+        .collect::<HashMap<_, _>>();
+    let mut calced = player
+        .history
+        .first()
+        .is_some_and(|entry| entry.old_rating.deviation <= db.config.maximum_calced_rd);
+    let rating_summary =
+        |old_rating: Glicko2Rating, new_rating: Glicko2Rating, calced: &mut bool| {
+            let old_player = SwitzerlandPlayer {
+                rating: old_rating,
+                calced: *calced,
+                ..Default::default()
+            };
+            *calced |= new_rating.deviation <= db.config.maximum_calced_rd;
+            let new_player = SwitzerlandPlayer {
+                rating: new_rating,
+                calced: *calced,
+                ..Default::default()
+            };
+            format_player_rank_summary(Some(&old_player), &new_player, false, show_rd)
+        };
 
-pub fn summarize_differences(
-    old_results: &SwitzerlandPlayerMap,
-    new_results: &Vec<SwitzerlandPlayer>,
-) {
-    for new_player in new_results {
-        let old_result = old_results.get(&new_player.id);
-        if let Some(old_result) = old_result
-            && old_result.rating == new_player.rating
-        {
-            continue;
+    for (tournament_id, entries) in &player.history.iter().chunk_by(|x| x.tournament_id) {
+        let entries = entries.collect_vec();
+        let first = entries.first().unwrap();
+        let last = entries.last().unwrap();
+        println!(
+            "- {} (tournament {tournament_id}): {}",
+            first.date.format("%Y-%m-%d"),
+            rating_summary(first.old_rating, last.new_rating, &mut calced.clone())
+        );
+        for entry in entries {
+            let opponent = entry
+                .opponents
+                .iter()
+                .map(|opponent| {
+                    display_names.get(opponent).cloned().unwrap_or_else(|| {
+                        SwitzerlandPlayer {
+                            id: opponent.clone(),
+                            ..Default::default()
+                        }
+                        .display_name()
+                        .into_owned()
+                        .into()
+                    })
+                })
+                .join(" & ");
+            println!(
+                "  - {} vs {opponent}: {}",
+                match entry.result {
+                    SetResult::Win => "Win",
+                    SetResult::Loss => "Loss",
+                },
+                rating_summary(entry.old_rating, entry.new_rating, &mut calced)
+            );
         }
-        print_player_simply(old_result, new_player, true, true);
     }
 }
 
-pub fn print_player_simply(
-    old_player: Option<&SwitzerlandPlayer>,
-    new_player: &SwitzerlandPlayer,
-    show_rank: bool,
-    show_rd: bool,
+pub fn print_set_prediction(
+    config: &SeasonConfig,
+    player1: &SwitzerlandPlayer,
+    player2: &SwitzerlandPlayer,
+    best_of: u32,
 ) {
+    let prediction = rating::predict_set(config, player1, player2, best_of);
     println!(
-        "{}",
-        format_player_simply(old_player, new_player, show_rank, show_rd)
+        "{} ({}) vs {} ({})",
+        player1.display_name(),
+        format_sp(player1.rating, true),
+        player2.display_name(),
+        format_sp(player2.rating, true),
     );
-}
-
-pub fn format_player_simply(
-    old_player: Option<&SwitzerlandPlayer>,
-    new_player: &SwitzerlandPlayer,
-    show_rank: bool,
-    show_rd: bool,
-) -> String {
-    format!(
-        "- {}: {}",
-        new_player.display_name(),
-        format_player_rank_summary(old_player, new_player, show_rank, show_rd)
-    )
-}
-
-pub fn format_player_rank_summary(
-    old_player: Option<&SwitzerlandPlayer>,
-    new_player: &SwitzerlandPlayer,
-    show_rank: bool,
-    show_rd: bool,
-) -> String {
-    if let Some(old_player) = old_player.filter(|p| show_rd || p.calced) {
-        format!(
-            "{} → {} ({:+.1}){}",
-            format_sp(old_player.rating, show_rd),
-            format_sp(new_player.rating, show_rd),
-            new_player.rating.rating - old_player.rating.rating,
-            if show_rank {
-                match (old_player.rank, new_player.rank) {
-                    (Some(old_rank), Some(new_rank)) => {
-                        format!(
-                            "; {}",
-                            match new_rank.cmp(&old_rank) {
-                                Ordering::Equal => format!("#{new_rank} ⇒"),
-                                Ordering::Less => format!("#{old_rank} → #{new_rank} ⇑"),
-                                Ordering::Greater => format!("#{old_rank} → #{new_rank} ⇓"),
-                            }
-                        )
-                    }
-                    (None, Some(new_rank)) => format!("; #{}", new_rank.get()),
-                    (_, None) => "".to_string(),
-                }
-            } else {
-                "".to_string()
-            }
-        )
-    } else {
-        format!(
-            "{}{}",
-            format_sp(new_player.rating, show_rd),
-            if show_rank && let Some(rank) = new_player.rank {
-                format!("; #{}", rank)
-            } else {
-                "".to_string()
-            }
-        )
+    println!(
+        "Win probability: {} {:.1}%, {} {:.1}%",
+        player1.display_name(),
+        prediction.win_probability * 100.0,
+        player2.display_name(),
+        (1.0 - prediction.win_probability) * 100.0,
+    );
+    for outcome in prediction.outcomes {
+        let (new_rating1, new_rating2) = outcome.new_ratings;
+        let winner = match outcome.result {
+            SetResult::Win => player1,
+            SetResult::Loss => player2,
+        };
+        let score = match outcome.score {
+            Some((score1, score2)) => format!(" {}–{}", score1.max(score2), score1.min(score2)),
+            None => String::new(),
+        };
+        println!(
+            "If {} wins{score}: {} {:+.1} SP, {} {:+.1} SP",
+            winner.display_name(),
+            player1.display_name(),
+            new_rating1.rating - player1.rating.rating,
+            player2.display_name(),
+            new_rating2.rating - player2.rating.rating,
+        );
     }
 }
-
-pub fn format_sp(rating: Glicko2Rating, show_rd: bool) -> String {
-    let scaled_power = (rating.rating * 10.0).floor().abs() as u32;
-    format!(
-        "{}{}.{} SP{}",
-        if rating.rating < 0.0 { "-" } else { "" },
-        scaled_power / 10,
-        scaled_power % 10,
-        if show_rd {
-            format!(" (RD {})", rating.deviation as i64)
-        } else {
-            "".to_string()
-        },
-    )
-}
@@ -1,10 +1,8 @@
 use crate::Result;
 use crate::db::{Database, PlayerId};
-use crate::sendou::schema::SendouUserRoot;
-use crate::sendou::turbo_stream::TurboStreamed;
+use crate::sendou::api::SendouClient;
 use ansi_term::Color;
 use itertools::Itertools;
-use reqwest::Client;
 use std::io;
 use std::io::Write;
 use std::path::Path;
@@ -42,7 +40,7 @@ pub async fn migration_cli(
         }
     )));
 
-    let client = Client::new();
+    let client = SendouClient::from_env()?;
     let mut players_map = db.clone().into_map();
     let mut player_name = String::new();
 
@@ -63,7 +61,7 @@ pub async fn migration_cli(
                     if player_slug.is_empty() {
                         break None;
                     }
-                    match request_player_info(&client, player_slug).await {
+                    match client.user(player_slug).await {
                         Ok(user) => {
                             println!(
                                 "Found player '{}' with ID {}",
@@ -101,16 +99,6 @@ pub async fn migration_cli(
         players_map.insert(real_player.id.clone(), real_player);
     }
 
-    Database::new_from_map(players_map).write(out_db)?;
+    db.with_players(players_map).write(out_db)?;
     Ok(())
 }
-
-async fn request_player_info(client: &Client, slug: &str) -> Result<SendouUserRoot> {
-    Ok(client
-        .get(format!("https://sendou.ink/u/{slug}.data"))
-        .send()
-        .await?
-        .json::<TurboStreamed<SendouUserRoot>>()
-        .await?
-        .0)
-}
@@ -0,0 +1,681 @@
+use crate::config::{RatingMode, SeasonConfig, TeamRatingStrategy};
+use crate::db::{
+    PlayerId, RatingHistoryEntry, SetRecord, SetResult, SwitzerlandPlayer, SwitzerlandPlayerMap,
+    TournamentParticipant, TournamentRecord,
+};
+use itertools::Itertools;
+use skillratings::Outcomes;
+use skillratings::glicko2::{Glicko2Rating, decay_deviation, glicko2, glicko2_rating_period};
+use std::collections::HashMap;
+use std::{iter, slice};
+
+/// Adds the participants of a tournament to the players, decaying the deviation of returning players
+/// for each tournament they missed. New players are added as unrated.
+pub fn start_tournament<'a>(
+    config: &SeasonConfig,
+    players: &mut SwitzerlandPlayerMap,
+    participants: impl IntoIterator<Item = &'a TournamentParticipant>,
+) {
+    for player in players.values_mut() {
+        player.since_played += 1;
+    }
+    for participant in participants {
+        players
+            .entry(participant.player.clone())
+            .and_modify(|player| {
+                // since_played will be 1 above the desired value due to the increment above
+                for _ in 1..player.since_played {
+                    player.rating = decay_deviation(&player.rating);
+                }
+                player.since_played = 0;
+            })
+            .or_insert_with(|| SwitzerlandPlayer {
+                id: participant.player.clone(),
+                rating: config
+                    .seeding
+                    .starting_rating(participant.seeding_skill_ordinal),
+                unrated: true,
+                ..Default::default()
+            })
+            .display_name = participant.display_name.clone();
+    }
+}
+
+/// Rates a set from player 1's perspective. The map score is only used with [`RatingMode::Maps`].
+pub fn rate_set(
+    config: &SeasonConfig,
+    rating1: &Glicko2Rating,
+    rating2: &Glicko2Rating,
+    result: SetResult,
+    score: Option<(u32, u32)>,
+) -> (Glicko2Rating, Glicko2Rating) {
+    let glicko2_config = config.glicko2();
+    let outcomes = set_outcomes(config, result, score);
+    if let [outcome] = outcomes.as_slice() {
+        return glicko2(rating1, rating2, outcome, &glicko2_config);
+    }
+    let results = |opponent: &Glicko2Rating, invert| {
+        outcomes
+            .iter()
+            .map(|&outcome| {
+                (
+                    *opponent,
+                    if invert {
+                        invert_outcome(outcome)
+                    } else {
+                        outcome
+                    },
+                )
+            })
+            .collect_vec()
+    };
+    (
+        glicko2_rating_period(rating1, &results(rating2, false), &glicko2_config),
+        glicko2_rating_period(rating2, &results(rating1, true), &glicko2_config),
+    )
+}
+
+/// The Glicko-2 outcomes of a set from player 1's perspective
+fn set_outcomes(
+    config: &SeasonConfig,
+    result: SetResult,
+    score: Option<(u32, u32)>,
+) -> Vec<Outcomes> {
+    match (config.rating_mode, score) {
+        (RatingMode::Maps, Some((score1, score2))) if score1 + score2 > 0 => {
+            iter::repeat_n(Outcomes::WIN, score1 as usize)
+                .chain(iter::repeat_n(Outcomes::LOSS, score2 as usize))
+                .collect()
+        }
+        _ => vec![match result {
+            SetResult::Win => Outcomes::WIN,
+            SetResult::Loss => Outcomes::LOSS,
+        }],
+    }
+}
+
+fn invert_outcome(outcome: Outcomes) -> Outcomes {
+    match outcome {
+        Outcomes::WIN => Outcomes::LOSS,
+        Outcomes::LOSS => Outcomes::WIN,
+        Outcomes::DRAW => Outcomes::DRAW,
+    }
+}
+
+/// The combined rating of a team, which is the average of its members' ratings
+pub fn team_rating(ratings: &[Glicko2Rating]) -> Glicko2Rating {
+    let count = ratings.len() as f64;
+    Glicko2Rating {
+        rating: ratings.iter().map(|x| x.rating).sum::<f64>() / count,
+        deviation: ratings.iter().map(|x| x.deviation).sum::<f64>() / count,
+        volatility: ratings.iter().map(|x| x.volatility).sum::<f64>() / count,
+    }
+}
+
+/// Applies the change in a team's combined rating to one of its members
+fn apply_team_change(
+    member: &Glicko2Rating,
+    old_team: &Glicko2Rating,
+    new_team: &Glicko2Rating,
+) -> Glicko2Rating {
+    if member == old_team {
+        return *new_team;
+    }
+    Glicko2Rating {
+        rating: member.rating + new_team.rating - old_team.rating,
+        deviation: member.deviation * new_team.deviation / old_team.deviation,
+        volatility: member.volatility + new_team.volatility - old_team.volatility,
+    }
+}
+
+/// Rates a set between two teams from team 1's perspective, returning the new rating of each member
+pub fn rate_team_set(
+    config: &SeasonConfig,
+    ratings1: &[Glicko2Rating],
+    ratings2: &[Glicko2Rating],
+    result: SetResult,
+    score: Option<(u32, u32)>,
+) -> (Vec<Glicko2Rating>, Vec<Glicko2Rating>) {
+    let team1 = team_rating(ratings1);
+    let team2 = team_rating(ratings2);
+    match config.team_rating {
+        TeamRatingStrategy::PerMember => (
+            ratings1
+                .iter()
+                .map(|rating| rate_set(config, rating, &team2, result, score).0)
+                .collect(),
+            ratings2
+                .iter()
+                .map(|rating| rate_set(config, &team1, rating, result, score).1)
+                .collect(),
+        ),
+        TeamRatingStrategy::Average => {
+            let (new_team1, new_team2) = rate_set(config, &team1, &team2, result, score);
+            (
+                ratings1
+                    .iter()
+                    .map(|rating| apply_team_change(rating, &team1, &new_team1))
+                    .collect(),
+                ratings2
+                    .iter()
+                    .map(|rating| apply_team_change(rating, &team2, &new_team2))
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// Rates all of a tournament's sets as a single Glicko-2 rating period, so that the result doesn't
+/// depend on the order sets were played in. Each set is rated against the ratings players had at
+/// the start of the tournament.
+pub struct TournamentRatingPeriod {
+    start_ratings: HashMap<PlayerId, Glicko2Rating>,
+    /// The results of each group of players that is rated together, as decided by the
+    /// [`TeamRatingStrategy`]
+    results: HashMap<Vec<PlayerId>, Vec<(Glicko2Rating, Outcomes)>>,
+}
+
+impl TournamentRatingPeriod {
+    pub fn new(players: &SwitzerlandPlayerMap) -> Self {
+        Self {
+            start_ratings: players
+                .iter()
+                .map(|(id, player)| (id.clone(), player.rating))
+                .collect(),
+            results: HashMap::new(),
+        }
+    }
+
+    /// Adds a set to the rating period, returning the ratings of both teams' members from every
+    /// set they've played in the period so far
+    pub fn add_set(
+        &mut self,
+        config: &SeasonConfig,
+        set: &SetRecord,
+    ) -> (Vec<Glicko2Rating>, Vec<Glicko2Rating>) {
+        let start_ratings = |team: &[PlayerId]| {
+            team.iter()
+                .map(|player| self.start_ratings[player])
+                .collect_vec()
+        };
+        let ratings1 = start_ratings(&set.team1);
+        let ratings2 = start_ratings(&set.team2);
+        let team1 = team_rating(&ratings1);
+        let team2 = team_rating(&ratings2);
+
+        for outcome in set_outcomes(config, set.result, Some(set.score)) {
+            for unit in rating_units(config, &set.team1) {
+                self.results
+                    .entry(unit.to_vec())
+                    .or_default()
+                    .push((team2, outcome));
+            }
+            for unit in rating_units(config, &set.team2) {
+                self.results
+                    .entry(unit.to_vec())
+                    .or_default()
+                    .push((team1, invert_outcome(outcome)));
+            }
+        }
+
+        let glicko2_config = config.glicko2();
+        let rate = |team: &[PlayerId], ratings: &[Glicko2Rating]| {
+            team.iter()
+                .zip(ratings)
+                .map(|(player, rating)| {
+                    let (unit, unit_rating) = match config.team_rating {
+                        TeamRatingStrategy::PerMember => (slice::from_ref(player), *rating),
+                        TeamRatingStrategy::Average => (team, team_rating(ratings)),
+                    };
+                    let new_unit_rating =
+                        glicko2_rating_period(&unit_rating, &self.results[unit], &glicko2_config);
+                    apply_team_change(rating, &unit_rating, &new_unit_rating)
+                })
+                .collect_vec()
+        };
+        (rate(&set.team1, &ratings1), rate(&set.team2, &ratings2))
+    }
+}
+
+/// The groups of players on a team that are rated together
+fn rating_units<'a>(config: &SeasonConfig, team: &'a [PlayerId]) -> Vec<&'a [PlayerId]> {
+    match config.team_rating {
+        TeamRatingStrategy::PerMember => team.chunks(1).collect(),
+        TeamRatingStrategy::Average => vec![team],
+    }
+}
+
+/// Rates a set, either on its own or as part of the tournament's rating period
+pub fn rate_tournament_set(
+    config: &SeasonConfig,
+    period: Option<&mut TournamentRatingPeriod>,
+    set: &SetRecord,
+    ratings1: &[Glicko2Rating],
+    ratings2: &[Glicko2Rating],
+) -> (Vec<Glicko2Rating>, Vec<Glicko2Rating>) {
+    match period {
+        Some(period) => period.add_set(config, set),
+        None => rate_team_set(config, ratings1, ratings2, set.result, Some(set.score)),
+    }
+}
+
+/// The chance of player 1 winning a set against player 2, and how both players' ratings would
+/// change with each possible result
+#[derive(Debug, Clone)]
+pub struct SetPrediction {
+    pub win_probability: f64,
+    pub outcomes: Vec<PredictedOutcome>,
+}
+
+/// One possible result of a predicted set, from player 1's perspective
+#[derive(Debug, Copy, Clone)]
+pub struct PredictedOutcome {
+    pub result: SetResult,
+    /// The map score, with [`RatingMode::Maps`] where it affects the new ratings
+    pub score: Option<(u32, u32)>,
+    pub new_ratings: (Glicko2Rating, Glicko2Rating),
+}
+
+/// Predicts a set between two players. With [`RatingMode::Maps`], every score a best-of-`best_of`
+/// set could end with is predicted, from the cleanest win to the cleanest loss.
+pub fn predict_set(
+    config: &SeasonConfig,
+    player1: &SwitzerlandPlayer,
+    player2: &SwitzerlandPlayer,
+    best_of: u32,
+) -> SetPrediction {
+    let scores = match config.rating_mode {
+        RatingMode::Set => vec![(SetResult::Win, None), (SetResult::Loss, None)],
+        RatingMode::Maps => {
+            let wins = best_of.div_ceil(2).max(1);
+            (0..wins)
+                .map(|losses| (SetResult::Win, Some((wins, losses))))
+                .chain(
+                    (0..wins)
+                        .rev()
+                        .map(|losses| (SetResult::Loss, Some((losses, wins)))),
+                )
+                .collect()
+        }
+    };
+    SetPrediction {
+        win_probability: player1.win_probability(player2),
+        outcomes: scores
+            .into_iter()
+            .map(|(result, score)| {
+                let (new_ratings1, new_ratings2) = rate_team_set(
+                    config,
+                    slice::from_ref(&player1.rating),
+                    slice::from_ref(&player2.rating),
+                    result,
+                    score,
+                );
+                PredictedOutcome {
+                    result,
+                    score,
+                    new_ratings: (new_ratings1[0], new_ratings2[0]),
+                }
+            })
+            .collect(),
+    }
+}
+
+pub fn apply_rating(
+    config: &SeasonConfig,
+    player: &mut SwitzerlandPlayer,
+    new_rating: Glicko2Rating,
+) {
+    player.rating = new_rating;
+    player.unrated = false;
+    if player.rating.deviation <= config.maximum_calced_rd {
+        player.calced = true;
+    }
+}
+
+/// Recomputes every player's rating from scratch by replaying the recorded tournaments in
+/// chronological order
+pub fn rebuild(config: &SeasonConfig, tournaments: &[TournamentRecord]) -> SwitzerlandPlayerMap {
+    rebuild_observed(config, tournaments, |_, _, _| {})
+}
+
+/// Like [`rebuild`], but calls `on_set` with the combined ratings of both teams before each set is
+/// rated
+pub fn rebuild_observed(
+    config: &SeasonConfig,
+    tournaments: &[TournamentRecord],
+    mut on_set: impl FnMut(&SetRecord, &Glicko2Rating, &Glicko2Rating),
+) -> SwitzerlandPlayerMap {
+    let mut players = SwitzerlandPlayerMap::new();
+    for tournament in tournaments.iter().sorted_by_key(|x| x.date) {
+        start_tournament(config, &mut players, &tournament.participants);
+        let mut period = config
+            .tournament_rating_period
+            .then(|| TournamentRatingPeriod::new(&players));
+        for set in &tournament.sets {
+            let ratings = |team: &[PlayerId]| {
+                team.iter()
+                    .map(|player| players.get(player).map(|x| x.rating))
+                    .collect::<Option<Vec<_>>>()
+            };
+            let (Some(ratings1), Some(ratings2)) = (ratings(&set.team1), ratings(&set.team2))
+            else {
+                continue;
+            };
+            on_set(set, &team_rating(&ratings1), &team_rating(&ratings2));
+            let (new_ratings1, new_ratings2) =
+                rate_tournament_set(config, period.as_mut(), set, &ratings1, &ratings2);
+            let mut update_team = |team: &[PlayerId],
+                                   opponents: &[PlayerId],
+                                   new_ratings: Vec<Glicko2Rating>,
+                                   result| {
+                for (player, new_rating) in team.iter().zip_eq(new_ratings) {
+                    let player = players.get_mut(player).unwrap();
+                    player.history.push(RatingHistoryEntry {
+                        tournament_id: tournament.id,
+                        match_id: set.match_id,
+                        date: tournament.date,
+                        old_rating: player.rating,
+                        new_rating,
+                        opponents: opponents.to_vec(),
+                        result,
+                    });
+                    apply_rating(config, player, new_rating);
+                }
+            };
+            update_team(&set.team1, &set.team2, new_ratings1, set.result);
+            update_team(&set.team2, &set.team1, new_ratings2, set.result.inverse());
+        }
+        // Players who never played are not saved to the database
+        players.retain(|_, player| !player.unrated);
+    }
+    players
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{RatingMode, SeasonConfig, TeamRatingStrategy};
+    use crate::db::{
+        PlayerId, SetRecord, SetResult, SwitzerlandPlayer, TournamentParticipant, TournamentRecord,
+    };
+    use crate::rating::{predict_set, rate_set, rate_team_set, rebuild};
+    use chrono::{TimeZone, Utc};
+    use itertools::Itertools;
+    use skillratings::Outcomes;
+    use skillratings::glicko2::{
+        Glicko2Rating, decay_deviation, expected_score, glicko2, glicko2_rating_period,
+    };
+
+    #[test]
+    fn rebuild_test() {
+        let participant = |id, seeding_skill_ordinal| TournamentParticipant {
+            player: PlayerId::Sendou(id),
+            display_name: Some(format!("Player {id}")),
+            seeding_skill_ordinal,
+        };
+        let set = |match_id, player1, player2, result| SetRecord {
+            match_id,
+            team1: vec![PlayerId::Sendou(player1)],
+            team2: vec![PlayerId::Sendou(player2)],
+            result,
+            score: (0, 0),
+        };
+        let tournaments = [
+            TournamentRecord {
+                id: 2,
+                date: Utc.with_ymd_and_hms(2026, 2, 1, 0, 0, 0).unwrap(),
+                participants: vec![participant(1, 0.0), participant(3, 0.0)],
+                sets: vec![set(20, 3, 1, SetResult::Win)],
+            },
+            TournamentRecord {
+                id: 1,
+                date: Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap(),
+                participants: vec![
+                    participant(1, 10.0),
+                    participant(2, -5.0),
+                    participant(4, 0.0),
+                ],
+                sets: vec![set(10, 1, 2, SetResult::Win)],
+            },
+            TournamentRecord {
+                id: 3,
+                date: Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap(),
+                participants: vec![participant(2, 0.0), participant(3, 0.0)],
+                sets: vec![set(30, 2, 3, SetResult::Loss)],
+            },
+        ];
+        let season_config = SeasonConfig {
+            tau: 0.4,
+            ..Default::default()
+        };
+        let players = rebuild(&season_config, &tournaments);
+
+        let config = season_config.glicko2();
+        let starting_rating = |ordinal| season_config.seeding.starting_rating(ordinal);
+        let (rate1, rate2) = glicko2(
+            &starting_rating(10.0),
+            &starting_rating(-5.0),
+            &Outcomes::WIN,
+            &config,
+        );
+        let (rate3, rate1) = glicko2(&starting_rating(0.0), &rate1, &Outcomes::WIN, &config);
+        let (rate2, rate3) = glicko2(&decay_deviation(&rate2), &rate3, &Outcomes::LOSS, &config);
+
+        assert_eq!(players[&PlayerId::Sendou(1)].rating, rate1);
+        assert_eq!(players[&PlayerId::Sendou(2)].rating, rate2);
+        assert_eq!(players[&PlayerId::Sendou(3)].rating, rate3);
+        assert!(!players.contains_key(&PlayerId::Sendou(4)));
+
+        assert_eq!(players[&PlayerId::Sendou(1)].since_played, 1);
+        assert_eq!(players[&PlayerId::Sendou(3)].history.len(), 2);
+        assert_eq!(
+            players[&PlayerId::Sendou(3)].history[1].result,
+            SetResult::Win
+        );
+    }
+
+    #[test]
+    fn maps_rating_mode_test() {
+        let config = SeasonConfig {
+            rating_mode: RatingMode::Maps,
+            ..Default::default()
+        };
+        let rating = Glicko2Rating::default();
+        let rate = |score| rate_set(&config, &rating, &rating, SetResult::Win, score);
+
+        let (close_win, close_loss) = rate(Some((3, 2)));
+        let (clean_win, clean_loss) = rate(Some((3, 0)));
+        assert!(close_win.rating > rating.rating);
+        assert!(close_win.rating < clean_win.rating);
+        assert!(close_loss.rating > clean_loss.rating);
+
+        let set_config = SeasonConfig::default();
+        assert_eq!(
+            rate(None),
+            rate_set(&set_config, &rating, &rating, SetResult::Win, None)
+        );
+        assert_eq!(rate(Some((0, 0))), rate(None));
+    }
+
+    #[test]
+    fn team_set_test() {
+        let rating = |rating| Glicko2Rating {
+            rating,
+            ..Default::default()
+        };
+        let team1 = [rating(1600.0), rating(1400.0)];
+        let team2 = [rating(1500.0), rating(1500.0)];
+
+        let config = SeasonConfig::default();
+        let (new_team1, new_team2) = rate_team_set(&config, &team1, &team2, SetResult::Win, None);
+        assert_eq!(
+            new_team1[0],
+            rate_set(&config, &team1[0], &rating(1500.0), SetResult::Win, None).0
+        );
+        assert!(new_team1[1].rating - 1400.0 > new_team1[0].rating - 1600.0);
+        assert_eq!(new_team2[0], new_team2[1]);
+
+        let config = SeasonConfig {
+            team_rating: TeamRatingStrategy::Average,
+            ..Default::default()
+        };
+        let (new_team1, _) = rate_team_set(&config, &team1, &team2, SetResult::Win, None);
+        let (new_average, _) = rate_set(
+            &config,
+            &rating(1500.0),
+            &rating(1500.0),
+            SetResult::Win,
+            None,
+        );
+        for (old, new) in team1.iter().zip(new_team1) {
+            assert!((new.rating - old.rating - (new_average.rating - 1500.0)).abs() < 1e-9);
+        }
+
+        let solo = [rating(1550.0)];
+        assert_eq!(
+            rate_team_set(&config, &solo, &team2[..1], SetResult::Loss, None),
+            {
+                let (new1, new2) = rate_set(&config, &solo[0], &team2[0], SetResult::Loss, None);
+                (vec![new1], vec![new2])
+            }
+        );
+    }
+
+    #[test]
+    fn tournament_rating_period_test() {
+        let participants = (1..=3)
+            .map(|id| TournamentParticipant {
+                player: PlayerId::Sendou(id),
+                display_name: None,
+                seeding_skill_ordinal: id as f64,
+            })
+            .collect_vec();
+        let set = |match_id, player1, player2| SetRecord {
+            match_id,
+            team1: vec![PlayerId::Sendou(player1)],
+            team2: vec![PlayerId::Sendou(player2)],
+            result: SetResult::Win,
+            score: (3, 1),
+        };
+        let tournament = |sets| TournamentRecord {
+            id: 1,
+            date: Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap(),
+            participants: participants.clone(),
+            sets,
+        };
+        let config = SeasonConfig {
+            tournament_rating_period: true,
+            ..Default::default()
+        };
+
+        let players = rebuild(&config, &[tournament(vec![set(1, 1, 2), set(2, 2, 3)])]);
+        let reordered = rebuild(&config, &[tournament(vec![set(2, 2, 3), set(1, 1, 2)])]);
+        for id in 1..=3 {
+            let id = PlayerId::Sendou(id);
+            assert_eq!(players[&id].rating, reordered[&id].rating);
+        }
+
+        let seeding = |ordinal| config.seeding.starting_rating(ordinal);
+        assert_eq!(
+            players[&PlayerId::Sendou(2)].rating,
+            glicko2_rating_period(
+                &seeding(2.0),
+                &[
+                    (seeding(1.0), Outcomes::LOSS),
+                    (seeding(3.0), Outcomes::WIN)
+                ],
+                &config.glicko2(),
+            )
+        );
+    }
+
+    #[test]
+    fn predict_set_test() {
+        let player = |rating, deviation| SwitzerlandPlayer {
+            rating: Glicko2Rating {
+                rating,
+                deviation,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let player1 = player(1650.0, 80.0);
+        let player2 = player(1500.0, 200.0);
+
+        let config = SeasonConfig::default();
+        let expected = expected_score(&player1.rating, &player2.rating).0;
+        assert_eq!(player1.win_probability(&player2), expected);
+        assert!(expected > 0.5);
+        assert_eq!(player2.win_probability(&player1), 1.0 - expected);
+
+        let prediction = predict_set(&config, &player1, &player2, 3);
+        assert_eq!(prediction.win_probability, expected);
+        let outcomes = prediction
+            .outcomes
+            .iter()
+            .map(|outcome| (outcome.result, outcome.score, outcome.new_ratings))
+            .collect_vec();
+        let glicko2_config = config.glicko2();
+        assert_eq!(
+            outcomes,
+            [
+                (
+                    SetResult::Win,
+                    None,
+                    glicko2(
+                        &player1.rating,
+                        &player2.rating,
+                        &Outcomes::WIN,
+                        &glicko2_config
+                    )
+                ),
+                (
+                    SetResult::Loss,
+                    None,
+                    glicko2(
+                        &player1.rating,
+                        &player2.rating,
+                        &Outcomes::LOSS,
+                        &glicko2_config
+                    )
+                ),
+            ]
+        );
+
+        let config = SeasonConfig {
+            rating_mode: RatingMode::Maps,
+            ..Default::default()
+        };
+        let prediction = predict_set(&config, &player1, &player2, 3);
+        assert_eq!(
+            prediction
+                .outcomes
+                .iter()
+                .map(|outcome| (outcome.result, outcome.score))
+                .collect_vec(),
+            [
+                (SetResult::Win, Some((2, 0))),
+                (SetResult::Win, Some((2, 1))),
+                (SetResult::Loss, Some((1, 2))),
+                (SetResult::Loss, Some((0, 2))),
+            ]
+        );
+        assert_eq!(
+            prediction.outcomes[1].new_ratings.0,
+            glicko2_rating_period(
+                &player1.rating,
+                &[
+                    (player2.rating, Outcomes::WIN),
+                    (player2.rating, Outcomes::WIN),
+                    (player2.rating, Outcomes::LOSS),
+                ],
+                &config.glicko2(),
+            )
+        );
+        let gains = prediction
+            .outcomes
+            .iter()
+            .map(|outcome| outcome.new_ratings.0.rating)
+            .collect_vec();
+        assert!(gains.is_sorted_by(|a, b| a > b));
+    }
+}
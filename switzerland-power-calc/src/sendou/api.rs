@@ -0,0 +1,437 @@
+use crate::Result;
+use crate::error::{Error, ErrorKind};
+use crate::sendou::recorder::SnapshotRecorder;
+use crate::sendou::schema::{
+    MatchResult, SendouId, SendouUserRoot, ToMatchResponse, ToResponse, Tournament,
+};
+use crate::sendou::turbo_stream::TurboStreamed;
+use chrono::{DateTime, Utc};
+use reqwest::header::{
+    ETAG, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER,
+};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+pub const DEFAULT_BASE_URL: &str = "https://sendou.ink";
+/// How long to wait before the first retry of a failed request
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// How long to back off after a 429 response without a `Retry-After` header
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(30);
+const USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    " (https://github.com/Gaming32/switzerland-power-calc, ",
+    env!("CARGO_PKG_VERSION"),
+    ")"
+);
+
+/// A client for the sendou.ink API. Requests that fail are retried with exponential backoff, and
+/// responses are decoded from turbo-stream.
+#[derive(Clone)]
+pub struct SendouClient {
+    http_client: Client,
+    base_url: String,
+    write_token: Option<String>,
+    retry_delay: Duration,
+    recorder: Option<Arc<SnapshotRecorder>>,
+    diagnostics: Option<Arc<SnapshotRecorder>>,
+}
+
+impl SendouClient {
+    pub fn new(base_url: &str) -> Result<Self> {
+        Ok(Self {
+            http_client: Client::builder().user_agent(USER_AGENT).build()?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            write_token: None,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            recorder: None,
+            diagnostics: None,
+        })
+    }
+
+    /// Creates a client for the server in `SENDOU_BASE_URL` (defaulting to sendou.ink), using
+    /// `SENDOU_WRITE_TOKEN` if it's set
+    pub fn from_env() -> Result<Self> {
+        let base_url = dotenvy::var("SENDOU_BASE_URL")
+            .ok()
+            .filter(|url| !url.is_empty());
+        let mut client = Self::new(base_url.as_deref().unwrap_or(DEFAULT_BASE_URL))?;
+        client.write_token = dotenvy::var("SENDOU_WRITE_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+        Ok(client)
+    }
+
+    #[cfg(test)]
+    pub fn with_retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Archives every tournament and match response with `recorder`
+    pub fn with_recorder(mut self, recorder: Option<Arc<SnapshotRecorder>>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Saves tournaments that can't be parsed with `diagnostics`
+    pub fn with_diagnostics(mut self, diagnostics: Option<Arc<SnapshotRecorder>>) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    pub fn has_diagnostics(&self) -> bool {
+        self.diagnostics.is_some()
+    }
+
+    /// Fetches a tournament. Returns `None` if it hasn't changed since `validators` were last
+    /// updated, either because the server said so or because the payload is the same. Pass
+    /// [`CacheValidators::default`] to always fetch the tournament.
+    pub async fn tournament(
+        &self,
+        tournament_id: SendouId,
+        validators: &mut CacheValidators,
+    ) -> Result<Option<Tournament>> {
+        let Some(payload) = self.tournament_payload(tournament_id, validators).await? else {
+            return Ok(None);
+        };
+
+        let mut hasher = DefaultHasher::new();
+        payload.hash(&mut hasher);
+        let payload_hash = hasher.finish();
+        if validators.payload_hash == Some(payload_hash) {
+            return Ok(None);
+        }
+        validators.payload_hash = Some(payload_hash);
+
+        let result = parse_tournament(&payload);
+        if result.is_err()
+            && let Some(diagnostics) = &self.diagnostics
+        {
+            match diagnostics.record_invalid("tournament", &payload) {
+                Ok(path) => println!(
+                    "Saved tournament that couldn't be parsed to {}",
+                    path.display()
+                ),
+                Err(err) => println!("Failed to save tournament that couldn't be parsed: {err}"),
+            }
+        }
+        result.map(Some)
+    }
+
+    async fn tournament_payload(
+        &self,
+        tournament_id: SendouId,
+        validators: &mut CacheValidators,
+    ) -> Result<Option<Vec<u8>>> {
+        let url = format!(
+            "{}/to/{tournament_id}/register.data?_routes=features/tournament/routes/to.$id",
+            self.base_url
+        );
+        let (http_client, url, old_validators) = (&self.http_client, &url, &*validators);
+        let response = with_retries(self.retry_delay, move || async move {
+            let mut request = http_client.get(url);
+            if let Some(etag) = &old_validators.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &old_validators.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+            let response = check_rate_limit(request.send().await?)?;
+            if response.status() == StatusCode::NOT_MODIFIED {
+                return Ok(None);
+            }
+            let response = response.error_for_status()?;
+            let new_validators = (
+                response.headers().get(ETAG).cloned(),
+                response.headers().get(LAST_MODIFIED).cloned(),
+            );
+            Ok(Some((response.bytes().await?.to_vec(), new_validators)))
+        })
+        .await?;
+        Ok(response.map(|(payload, (etag, last_modified))| {
+            validators.etag = etag;
+            validators.last_modified = last_modified;
+            if let Some(recorder) = &self.recorder {
+                recorder.record_tournament(&payload);
+            }
+            payload
+        }))
+    }
+
+    pub async fn match_results(
+        &self,
+        tournament_id: SendouId,
+        match_id: SendouId,
+    ) -> Result<Vec<MatchResult>> {
+        let payload = self
+            .get(format!(
+                "{}/to/{tournament_id}/matches/{match_id}.data?_routes=features/tournament-match/routes/to.$id.matches.$mid",
+                self.base_url
+            ))
+            .await?;
+        if let Some(recorder) = &self.recorder {
+            recorder.record_match(match_id, &payload);
+        }
+        Ok(decode::<ToMatchResponse>(&payload)?.to_match.data.results)
+    }
+
+    pub async fn user(&self, slug: &str) -> Result<SendouUserRoot> {
+        let payload = self.get(format!("{}/u/{slug}.data", self.base_url)).await?;
+        decode(&payload)
+    }
+
+    /// Sets the seeding order of a tournament's teams. This requires a write token.
+    pub async fn set_seeds(&self, tournament_id: SendouId, team_ids: &[SendouId]) -> Result<()> {
+        let write_token = self
+            .write_token
+            .as_ref()
+            .ok_or_else(|| ErrorKind::MissingEnv("SENDOU_WRITE_TOKEN".to_string()))?;
+        let url = format!("{}/api/tournament/{tournament_id}/seeds", self.base_url);
+        let (http_client, url) = (&self.http_client, &url);
+        // Setting the same seeds again is harmless, so this is retried like any other request
+        with_retries(self.retry_delay, move || async move {
+            let request = http_client.post(url).bearer_auth(write_token).json(&json!({
+                "tournamentTeamIds": team_ids,
+            }));
+            send(request).await
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn get(&self, url: String) -> Result<Vec<u8>> {
+        let (http_client, url) = (&self.http_client, &url);
+        with_retries(self.retry_delay, move || async move {
+            Ok(send(http_client.get(url)).await?.bytes().await?.to_vec())
+        })
+        .await
+    }
+}
+
+/// The `ETag` and `Last-Modified` headers of a previous response, sent back to make a conditional
+/// request, along with a hash of its payload for servers that don't support them
+#[derive(Clone, Debug, Default)]
+pub struct CacheValidators {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    payload_hash: Option<u64>,
+}
+
+fn parse_tournament(payload: &[u8]) -> Result<Tournament> {
+    Ok(decode::<ToResponse>(payload)?.to.data.tournament)
+}
+
+fn decode<T: DeserializeOwned>(payload: &[u8]) -> Result<T> {
+    Ok(serde_json::from_slice::<TurboStreamed<T>>(payload)?.0)
+}
+
+async fn send(request: RequestBuilder) -> Result<Response> {
+    Ok(check_rate_limit(request.send().await?)?.error_for_status()?)
+}
+
+/// Calls `request` up to 5 times, doubling the delay after each failure. Errors that won't be fixed
+/// by retrying, such as a response that couldn't be parsed or a 404, are returned immediately. If
+/// the server asks us to slow down, we wait at least as long as it asked.
+async fn with_retries<T, F: Future<Output = Result<T>>>(
+    initial_delay: Duration,
+    request: impl Fn() -> F,
+) -> Result<T> {
+    let mut delay = initial_delay;
+    for _ in 1..=4 {
+        let result = request().await;
+        let wait = match &result {
+            Ok(_) => return result,
+            Err(Error {
+                error: ErrorKind::Http(http),
+                ..
+            }) if http.is_decode() || http.status().is_some_and(|x| x.is_client_error()) => {
+                return result;
+            }
+            Err(Error {
+                error: ErrorKind::JsonSerialization(_),
+                ..
+            }) => return result,
+            Err(Error {
+                error: ErrorKind::RateLimited(retry_after),
+                ..
+            }) => {
+                let wait = delay.max(*retry_after);
+                println!(
+                    "Rate limited by sendou.ink, retrying in {}s",
+                    wait.as_secs()
+                );
+                wait
+            }
+            _ => delay,
+        };
+        sleep(wait).await;
+        delay *= 2;
+    }
+    request().await
+}
+
+/// Turns a 429 or 503 response into [`ErrorKind::RateLimited`], honouring its `Retry-After` header
+fn check_rate_limit(response: Response) -> Result<Response> {
+    if !matches!(
+        response.status(),
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return Ok(response);
+    }
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    match retry_after {
+        Some(retry_after) => Err(ErrorKind::RateLimited(retry_after).into()),
+        None if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+            Err(ErrorKind::RateLimited(DEFAULT_RETRY_AFTER).into())
+        }
+        None => Ok(response),
+    }
+}
+
+/// Parses a `Retry-After` header, which is either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    Some(
+        date.signed_duration_since(Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::ErrorKind;
+    use crate::sendou::api::{CacheValidators, parse_retry_after};
+    use crate::sendou::mock_server::{MockResponse, MockSendouServer};
+    use std::time::Duration;
+
+    const TOURNAMENT_PATH: &str =
+        "/to/1234/register.data?_routes=features/tournament/routes/to.$id";
+
+    #[tokio::test]
+    async fn fetch_fixtures_test() {
+        let server = MockSendouServer::with_fixtures();
+        let client = server.client();
+
+        let tournament = client
+            .tournament(1234, &mut CacheValidators::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tournament.context.name, "Mock Cup");
+        assert_eq!(tournament.context.teams.len(), 4);
+        assert_eq!(tournament.data.matches.len(), 6);
+
+        let results = client.match_results(1234, 11).await.unwrap();
+        assert_eq!(
+            results.iter().map(|x| x.winner_team_id).collect::<Vec<_>>(),
+            [1, 4, 1]
+        );
+
+        let user = client.user("alpha").await.unwrap();
+        assert_eq!((user.user.id, user.user.username.as_str()), (101, "Alpha"));
+
+        assert_eq!(
+            server.requested_paths(),
+            [
+                TOURNAMENT_PATH,
+                "/to/1234/matches/11.data?_routes=features/tournament-match/routes/to.$id.matches.$mid",
+                "/u/alpha.data",
+            ]
+        );
+        assert!(server.requests().iter().all(|x| {
+            x.header("user-agent")
+                .is_some_and(|agent| agent.starts_with(env!("CARGO_PKG_NAME")))
+        }));
+    }
+
+    #[tokio::test]
+    async fn retry_test() {
+        let server = MockSendouServer::with_fixtures();
+        server.queue(TOURNAMENT_PATH, MockResponse::status(503));
+        server.queue(TOURNAMENT_PATH, MockResponse::status(500));
+        let client = server.client();
+
+        let tournament = client
+            .tournament(1234, &mut CacheValidators::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tournament.context.id, 1234);
+        assert_eq!(server.requested_paths().len(), 3);
+
+        server.queue(TOURNAMENT_PATH, MockResponse::ok("not turbo-stream"));
+        assert!(
+            client
+                .tournament(1234, &mut CacheValidators::default())
+                .await
+                .is_err()
+        );
+        assert_eq!(server.requested_paths().len(), 4);
+
+        server.queue(
+            TOURNAMENT_PATH,
+            MockResponse::status(429).with_header("Retry-After", "0"),
+        );
+        let tournament = client
+            .tournament(1234, &mut CacheValidators::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tournament.context.id, 1234);
+        assert_eq!(server.requested_paths().len(), 6);
+
+        assert!(client.user("nobody").await.is_err());
+        assert_eq!(server.requested_paths().len(), 7);
+
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[tokio::test]
+    async fn set_seeds_test() {
+        let server = MockSendouServer::with_fixtures();
+        let result = server.client().set_seeds(1234, &[3, 1, 2]).await;
+        assert!(matches!(
+            result.unwrap_err().error,
+            ErrorKind::MissingEnv(_)
+        ));
+        assert!(server.requests().is_empty());
+
+        server.queue(
+            "/api/tournament/1234/seeds",
+            MockResponse::status(503).with_header("Retry-After", "0"),
+        );
+        let mut client = server.client();
+        client.write_token = Some("token".to_string());
+        client.set_seeds(1234, &[3, 1, 2]).await.unwrap();
+
+        let requests = server.requests();
+        let [_, request] = requests.as_slice() else {
+            panic!("Expected a retried request, got {requests:?}");
+        };
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/api/tournament/1234/seeds");
+        assert_eq!(request.header("authorization"), Some("Bearer token"));
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&request.body).unwrap(),
+            serde_json::json!({"tournamentTeamIds": [3, 1, 2]})
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use crate::Result;
+use crate::db::Database;
+use crate::sendou::api::SendouClient;
+use crate::sendou::discord::{DiscordBot, DiscordOutput};
+use crate::sendou::lang::CommandIdDisplay;
+use crate::sendou::notify::SinkOptions;
+use crate::sendou::overlay::OverlayServer;
+use crate::sendou::player_commands::PlayerCommands;
+use crate::sendou::recorder::SnapshotRecorder;
+use crate::sendou::run_live_tournament;
+use crate::sendou::run_state::RunStateFile;
+use crate::sendou::schema::SendouId;
+use itertools::Itertools;
+use serenity::all::{
+    CacheHttp, CommandId, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage, GuildId,
+    Permissions,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{mpsc, oneshot};
+
+const DB_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum ModeratorCommand {
+    Process,
+    Cleanup,
+}
+
+impl ModeratorCommand {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Process => "process",
+            Self::Cleanup => "cleanup",
+        }
+    }
+
+    fn create(self) -> CreateCommand {
+        let command =
+            CreateCommand::new(self.name()).default_member_permissions(Permissions::MANAGE_GUILD);
+        match self {
+            Self::Process => command
+                .description("Starts processing a sendou.ink tournament")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "tournament",
+                        "The ID of the tournament, as in https://sendou.ink/to/<ID>",
+                    )
+                    .required(true)
+                    .min_int_value(1),
+                ),
+            Self::Cleanup => command.description(
+                "Deletes the player channels of the tournament that was just processed",
+            ),
+        }
+    }
+}
+
+/// Slash commands that let moderators process tournaments without running the CLI
+pub struct ModeratorCommands {
+    command_ids: RwLock<HashMap<CommandId, ModeratorCommand>>,
+    tournaments: mpsc::UnboundedSender<SendouId>,
+    /// The tournament that's being processed or is about to be, if any
+    current_tournament: Mutex<Option<SendouId>>,
+    cleanup: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+impl ModeratorCommands {
+    /// Creates the commands, which send tournaments that should be processed to `tournaments`
+    pub fn new(tournaments: mpsc::UnboundedSender<SendouId>) -> Self {
+        Self {
+            command_ids: RwLock::new(HashMap::new()),
+            tournaments,
+            current_tournament: Mutex::new(None),
+            cleanup: Mutex::new(None),
+        }
+    }
+
+    pub async fn create_commands(&self, http: impl CacheHttp, guild_id: GuildId) -> Result<()> {
+        for command in [ModeratorCommand::Process, ModeratorCommand::Cleanup] {
+            let command_id = guild_id.create_command(&http, command.create()).await?.id;
+            self.command_ids
+                .write()
+                .unwrap()
+                .insert(command_id, command);
+        }
+        Ok(())
+    }
+
+    pub async fn delete_commands(&self, http: impl CacheHttp, guild_id: GuildId) -> Result<()> {
+        let command_ids = self
+            .command_ids
+            .write()
+            .unwrap()
+            .drain()
+            .map(|(command_id, _)| command_id)
+            .collect_vec();
+        for command_id in command_ids {
+            guild_id.delete_command(http.http(), command_id).await?;
+        }
+        Ok(())
+    }
+
+    /// How to mention the cleanup command in a message
+    pub fn cleanup_command_display(&self) -> String {
+        let name = ModeratorCommand::Cleanup.name();
+        self.command_ids
+            .read()
+            .unwrap()
+            .iter()
+            .find(|&(_, &command)| command == ModeratorCommand::Cleanup)
+            .map_or_else(
+                || format!("/{name}"),
+                |(&command_id, _)| CommandIdDisplay(name.into(), command_id).to_string(),
+            )
+    }
+
+    /// Queues `tournament_id` to be processed, unless another tournament already is. Returns the
+    /// tournament that's already being processed if so.
+    pub fn request_tournament(&self, tournament_id: SendouId) -> std::result::Result<(), SendouId> {
+        let mut current_tournament = self.current_tournament.lock().unwrap();
+        if let Some(current_tournament) = *current_tournament {
+            return Err(current_tournament);
+        }
+        *current_tournament = Some(tournament_id);
+        // The receiver lives as long as the bot does
+        let _ = self.tournaments.send(tournament_id);
+        Ok(())
+    }
+
+    pub fn tournament_finished(&self) {
+        *self.current_tournament.lock().unwrap() = None;
+    }
+
+    /// Returns a receiver that's sent to once a moderator uses the cleanup command
+    pub fn wait_for_cleanup(&self) -> oneshot::Receiver<()> {
+        let (sender, receiver) = oneshot::channel();
+        *self.cleanup.lock().unwrap() = Some(sender);
+        receiver
+    }
+
+    /// Responds to `command` if it's one of these commands, returning whether it was
+    pub async fn handle(&self, ctx: &Context, command: &CommandInteraction) -> bool {
+        let Some(moderator_command) = self
+            .command_ids
+            .read()
+            .unwrap()
+            .get(&command.data.id)
+            .copied()
+        else {
+            return false;
+        };
+        let response = match moderator_command {
+            ModeratorCommand::Process => {
+                let tournament_id = command
+                    .data
+                    .options
+                    .first()
+                    .and_then(|option| option.value.as_i64())
+                    .and_then(|id| SendouId::try_from(id).ok());
+                match tournament_id.map(|id| (id, self.request_tournament(id))) {
+                    Some((tournament_id, Ok(()))) => {
+                        format!("Processing tournament {tournament_id}...")
+                    }
+                    Some((_, Err(current_tournament))) => format!(
+                        "Tournament {current_tournament} is already being processed. Wait for it to finish first."
+                    ),
+                    None => "That isn't a valid tournament ID".to_string(),
+                }
+            }
+            ModeratorCommand::Cleanup => match self.cleanup.lock().unwrap().take() {
+                Some(cleanup) => {
+                    let _ = cleanup.send(());
+                    "Cleaning up player channels...".to_string()
+                }
+                None => "There are no player channels waiting to be cleaned up".to_string(),
+            },
+        };
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new().content(response),
+        );
+        if let Err(e) = command.create_response(ctx, response).await {
+            println!("Failed to respond to {} command: {e}", command.data.name);
+        }
+        true
+    }
+}
+
+/// Runs the Discord bot until it's stopped with Ctrl+C, processing tournaments when moderators ask
+/// it to. The database at `db_path` is replaced with the result of each tournament.
+#[tokio::main]
+pub async fn bot_cli(
+    db_path: &Path,
+    record_dir: Option<&Path>,
+    diagnostics_dir: Option<&Path>,
+    sinks: SinkOptions<'_>,
+) -> Result<()> {
+    let state_path = db_path.with_extension("state.json");
+    let (tournaments_send, mut tournaments) = mpsc::unbounded_channel();
+    let moderator_commands = Arc::new(ModeratorCommands::new(tournaments_send));
+    let player_commands = Arc::new(
+        PlayerCommands::new(Database::read(db_path)?, SendouClient::from_env()?)
+            .with_db_path(db_path),
+    );
+    let bot =
+        DiscordBot::connect(player_commands.clone(), Some(moderator_commands.clone())).await?;
+    // The overlay keeps serving between tournaments, so it's only started once
+    let overlay = match sinks.overlay_addr {
+        Some(addr) => Some(OverlayServer::start(addr).await?),
+        None => None,
+    };
+    let sinks = SinkOptions {
+        overlay_addr: None,
+        ..sinks
+    };
+
+    if let Some(tournament_id) = RunStateFile::pending_tournament(&state_path)? {
+        println!("Resuming tournament {tournament_id}");
+        let _ = moderator_commands.request_tournament(tournament_id);
+    }
+
+    let mut db_modified = fs::metadata(db_path)?.modified()?;
+    let mut db_check = tokio::time::interval(DB_CHECK_INTERVAL);
+    println!("Bot is running. Press Ctrl+C to stop.");
+    loop {
+        let tournament_id = tokio::select! {
+            Some(tournament_id) = tournaments.recv() => tournament_id,
+            _ = db_check.tick() => {
+                if let Err(err) =
+                    sync_database(&bot, &player_commands, db_path, &mut db_modified).await
+                {
+                    println!("Failed to sync with the database: {err}");
+                }
+                continue;
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        };
+
+        println!("Processing tournament {tournament_id}");
+        // Stopping partway through leaves the run state behind, so it's resumed on the next start
+        let result = tokio::select! {
+            result = process_tournament(
+                &bot,
+                db_path,
+                &state_path,
+                tournament_id,
+                record_dir,
+                diagnostics_dir,
+                &sinks,
+                overlay.as_ref(),
+            ) => result,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        bot.set_run_state(None);
+        moderator_commands.tournament_finished();
+        // Nothing that goes wrong here should stop the bot from handling the next tournament
+        if let Err(err) = result {
+            println!("Failed to process tournament {tournament_id}: {err}");
+            if let Err(err) = bot
+                .send_moderator_message(&format!(
+                    "Failed to process tournament {tournament_id}: {err}"
+                ))
+                .await
+            {
+                println!("Failed to send moderator message: {err}");
+            }
+        }
+        match Database::read(db_path) {
+            Ok(db) => player_commands.set_database(db),
+            Err(err) => println!("Failed to read the database: {err}"),
+        }
+        if let Ok(modified) = fs::metadata(db_path).and_then(|metadata| metadata.modified()) {
+            db_modified = modified;
+        }
+    }
+
+    println!("Shutting down");
+    bot.shutdown().await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn process_tournament(
+    bot: &DiscordBot,
+    db_path: &Path,
+    state_path: &Path,
+    tournament_id: SendouId,
+    record_dir: Option<&Path>,
+    diagnostics_dir: Option<&Path>,
+    sinks: &SinkOptions<'_>,
+    overlay: Option<&OverlayServer>,
+) -> Result<()> {
+    // The database from before the tournament is kept as a backup. If the run is resumed, it's
+    // processed from that again, as the database may have already been replaced.
+    let in_db = db_path.with_extension(format!("before-{tournament_id}.db"));
+    let resuming = RunStateFile::pending_tournament(state_path)? == Some(tournament_id);
+    if !resuming || !in_db.exists() {
+        if Database::read(db_path)?
+            .tournaments
+            .iter()
+            .any(|tournament| tournament.id == tournament_id)
+        {
+            return Err(format!("Tournament {tournament_id} has already been processed").into());
+        }
+        fs::copy(db_path, &in_db)?;
+    }
+
+    let recorder = record_dir
+        .map(|dir| SnapshotRecorder::new(&dir.join(tournament_id.to_string())))
+        .transpose()?
+        .map(Arc::new);
+    let diagnostics = diagnostics_dir
+        .map(SnapshotRecorder::new)
+        .transpose()?
+        .map(Arc::new);
+    let sendou = SendouClient::from_env()?
+        .with_recorder(recorder)
+        .with_diagnostics(diagnostics);
+    let run_state = Arc::new(RunStateFile::load(state_path, tournament_id)?);
+    let mut notifier = sinks.create_notifier(run_state.clone()).await?;
+    if let Some(overlay) = overlay {
+        overlay.clear();
+        notifier.add_sink(overlay.clone());
+    }
+    run_live_tournament(
+        DiscordOutput::new(bot, run_state.clone(), notifier),
+        &in_db,
+        db_path,
+        &sendou,
+        tournament_id,
+        &run_state,
+    )
+    .await
+}
+
+/// Picks up changes made to the database by anything other than the bot, such as a rebuild,
+/// updating the leaderboard if any ratings changed
+async fn sync_database(
+    bot: &DiscordBot,
+    player_commands: &PlayerCommands,
+    db_path: &Path,
+    db_modified: &mut SystemTime,
+) -> Result<()> {
+    let modified = fs::metadata(db_path)?.modified()?;
+    if modified == *db_modified {
+        return Ok(());
+    }
+    let new_db = Database::read(db_path)?;
+    *db_modified = modified;
+
+    let old_players = player_commands.database().into_map();
+    let ratings_changed = new_db.players.len() != old_players.len()
+        || new_db.players.iter().any(|player| {
+            old_players.get(&player.id).is_none_or(|old_player| {
+                old_player.rating != player.rating || old_player.display_name != player.display_name
+            })
+        });
+    if ratings_changed {
+        println!("Database changed, updating leaderboard");
+        bot.update_leaderboard(&old_players, &new_db, &HashMap::new())
+            .await?;
+    }
+    player_commands.set_database(new_db);
+    Ok(())
+}
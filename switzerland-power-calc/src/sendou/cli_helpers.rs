@@ -1,21 +1,19 @@
-use crate::db::{PlayerId, SwitzerlandPlayer, SwitzerlandPlayerMap};
+use crate::db::SwitzerlandPlayer;
 use ansi_term::Color;
 use itertools::Itertools;
 use std::cmp::Ordering;
 
 pub fn print_seeding_instructions<'a, Team, Iter, Format>(
-    players: &SwitzerlandPlayerMap,
     teams_iter: Iter,
     formatter: Format,
-) -> Vec<(&'a Team, &SwitzerlandPlayer)>
+) -> Vec<(&'a Team, SwitzerlandPlayer)>
 where
     Team: 'a,
-    Iter: IntoIterator<Item = (&'a Team, PlayerId)>,
+    Iter: IntoIterator<Item = (&'a Team, SwitzerlandPlayer)>,
     Format: Fn(&Team, &SwitzerlandPlayer) -> String,
 {
     let sorted_teams = teams_iter
         .into_iter()
-        .filter_map(|(team, name)| players.get(&name).map(|x| (team, x)))
         .filter(|(_, p)| p.rating.rating != 1500.0)
         .sorted_by(|(_, p1), (_, p2)| p1.descending_rating_order_cmp(p2))
         .collect_vec();
@@ -1,16 +1,43 @@
-use crate::sendou::lang::Language;
-use dashmap::DashMap;
+use crate::db::{Database, PlayerId, SwitzerlandPlayer, SwitzerlandPlayerMap};
+use crate::sendou::bot::ModeratorCommands;
+use crate::sendou::lang::{CommandIdDisplay, Language};
+use crate::sendou::leaderboard::generate_leaderboard_messages;
+use crate::sendou::notify::{NotificationSink, ProgressNotification, ProgressNotifier};
+use crate::sendou::output::{ProgressUpdate, TournamentOutput};
+use crate::sendou::player_commands::PlayerCommands;
+use crate::sendou::run_state::RunStateFile;
+use crate::sendou::types::{DiscordChannelsMap, GetTournamentFn, TeamsMap};
+use crate::sendou::{
+    env, env_str, format_link, format_results, show_placement_count, split_message,
+};
+use crate::{Result, format_player_rank_summary};
+use itertools::Itertools;
 use serenity::all::{
-    Cache, CacheHttp, CommandId, Context, CreateInteractionResponse,
-    CreateInteractionResponseMessage, EventHandler, GuildId, Http, Interaction, UserId,
+    ActivityData, Cache, CacheHttp, Channel, ChannelId, ChannelType, Client, CommandId,
+    CommandInteraction, CommandOptionType, Context, CreateAttachment, CreateChannel, CreateCommand,
+    CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateMessage, EditInteractionResponse, EventHandler, GatewayIntents, GuildChannel, GuildId,
+    Http, Interaction, Mentionable, MessageFlags, MessageId, PermissionOverwrite,
+    PermissionOverwriteType, Permissions, UserId,
 };
+use serenity::futures::TryStreamExt;
+use serenity::model::Timestamp;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex, RwLock};
 use tokio::sync::oneshot;
 
+const USER_CHANNEL_PERMS: Permissions = Permissions::VIEW_CHANNEL
+    .union(Permissions::SEND_MESSAGES)
+    .union(Permissions::USE_APPLICATION_COMMANDS);
+
 pub struct DiscordEventHandler {
     pub ready: Mutex<Option<oneshot::Sender<()>>>,
-    pub language_command: Arc<RwLock<Option<CommandId>>>,
-    pub language_output: Arc<DashMap<UserId, Language>>,
+    pub commands: Arc<BotCommands>,
 }
 
 #[serenity::async_trait]
@@ -25,26 +52,119 @@ impl EventHandler for DiscordEventHandler {
         let Some(command) = interaction.command() else {
             return;
         };
-        if Some(command.data.id) != *self.language_command.read().unwrap() {
+        self.commands.handle(&ctx, &command).await;
+    }
+}
+
+/// The slash commands the bot answers
+pub struct BotCommands {
+    language_command: RwLock<Option<CommandId>>,
+    player_commands: Arc<PlayerCommands>,
+    moderator_commands: Option<Arc<ModeratorCommands>>,
+    /// The state of the tournament being processed, if any, which chosen languages are saved to
+    run_state: RwLock<Option<Arc<RunStateFile>>>,
+}
+
+impl BotCommands {
+    async fn create(&self, http: &DiscordHttp, guild_id: GuildId) -> Result<()> {
+        let language_command_id = guild_id
+            .create_command(http, create_language_command())
+            .await?
+            .id;
+        *self.language_command.write().unwrap() = Some(language_command_id);
+        self.player_commands.create_commands(http, guild_id).await?;
+        if let Some(moderator_commands) = &self.moderator_commands {
+            moderator_commands.create_commands(http, guild_id).await?;
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, http: &DiscordHttp, guild_id: GuildId) -> Result<()> {
+        let language_command_id = self.language_command.write().unwrap().take();
+        if let Some(language_command_id) = language_command_id {
+            guild_id
+                .delete_command(http.http(), language_command_id)
+                .await?;
+        }
+        self.player_commands.delete_commands(http, guild_id).await?;
+        if let Some(moderator_commands) = &self.moderator_commands {
+            moderator_commands.delete_commands(http, guild_id).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle(&self, ctx: &Context, command: &CommandInteraction) {
+        let run_state = self.run_state.read().unwrap().clone();
+        let language = run_state.as_ref().and_then(|run_state| {
+            run_state.read(|state| state.languages.get(&command.user.id).copied())
+        });
+        if self.player_commands.handle(ctx, command, language).await {
+            return;
+        }
+        if let Some(moderator_commands) = &self.moderator_commands
+            && moderator_commands.handle(ctx, command).await
+        {
             return;
         }
+        if Some(command.data.id) == *self.language_command.read().unwrap() {
+            self.handle_language_command(ctx, command, run_state.as_deref())
+                .await;
+        }
+    }
+
+    async fn handle_language_command(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        run_state: Option<&RunStateFile>,
+    ) {
         let language = command.data.options.first().map_or_else(
             || Language::from_discord_id(&command.locale),
             |lang| lang.value.as_str().and_then(Language::from_id),
         );
+        // Saving the language can mean looking up the player's linked account, which can take
+        // longer than Discord waits for a response
+        let defer = CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new());
+        if let Err(e) = command.create_response(ctx, defer).await {
+            println!("Failed to send user language change response: {e}");
+            return;
+        }
         let response = if let Some(language) = language {
-            self.language_output.insert(command.user.id, language);
-            language.changed_language(language)
+            match self
+                .save_language(command.user.id, language, run_state)
+                .await
+            {
+                Ok(false) => language.language_not_saved(),
+                Ok(true) => language.changed_language(language),
+                Err(e) => {
+                    println!("Failed to save user language change: {e}");
+                    language.changed_language(language)
+                }
+            }
         } else {
             "Unfortunately your language is unsupported. Please run the command again and select one of the listed options.".into()
         };
-        let response = CreateInteractionResponse::Message(
-            CreateInteractionResponseMessage::new().content(response),
-        );
-        if let Err(e) = command.create_response(ctx, response).await {
+        let response = EditInteractionResponse::new().content(response);
+        if let Err(e) = command.edit_response(ctx, response).await {
             println!("Failed to send user language change response: {e}");
         };
     }
+
+    /// Saves the language a user chose, returning whether there was anywhere to save it
+    async fn save_language(
+        &self,
+        user_id: UserId,
+        language: Language,
+        run_state: Option<&RunStateFile>,
+    ) -> Result<bool> {
+        match run_state {
+            Some(run_state) => {
+                run_state.update(|state| state.languages.insert(user_id, language))?;
+                Ok(true)
+            }
+            None => self.player_commands.save_language(user_id, language).await,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -69,3 +189,620 @@ impl CacheHttp for DiscordHttp {
         Some(&self.0)
     }
 }
+
+/// A connection to the Discord server, which can outlive the processing of a single tournament
+pub struct DiscordBot {
+    client: Client,
+    http: DiscordHttp,
+    chat_category: GuildChannel,
+    leaderboard_channel: ChannelId,
+    moderator_channel: ChannelId,
+    commands: Arc<BotCommands>,
+}
+
+impl DiscordBot {
+    /// Connects to Discord using the bot and channels configured in the environment, and creates
+    /// its commands. Moderators can only process tournaments from Discord if `moderator_commands`
+    /// are given.
+    pub async fn connect(
+        player_commands: Arc<PlayerCommands>,
+        moderator_commands: Option<Arc<ModeratorCommands>>,
+    ) -> Result<Self> {
+        let (discord_ready_send, discord_ready) = oneshot::channel();
+        let commands = Arc::new(BotCommands {
+            language_command: RwLock::new(None),
+            player_commands,
+            moderator_commands,
+            run_state: RwLock::new(None),
+        });
+        let client = serenity::client::ClientBuilder::new(
+            env_str("DISCORD_BOT_TOKEN")?,
+            GatewayIntents::GUILDS | GatewayIntents::GUILD_MEMBERS,
+        )
+        .event_handler(DiscordEventHandler {
+            ready: Mutex::new(Some(discord_ready_send)),
+            commands: commands.clone(),
+        })
+        .activity(ActivityData::competing("Switzerland"))
+        .await?;
+        client.shard_manager.set_shards(0, 1, 1).await;
+        client.shard_manager.initialize()?;
+        discord_ready.await.unwrap();
+        let http = DiscordHttp::new(client.cache.clone(), client.http.clone());
+
+        let chat_category = match env::<ChannelId>("DISCORD_CHAT_CATEGORY_ID")?
+            .to_channel(&http)
+            .await?
+        {
+            Channel::Private(channel) => {
+                return Err(
+                    format!("Discord channel {} is not part of a guild", channel.name()).into(),
+                );
+            }
+            Channel::Guild(channel) if channel.kind != ChannelType::Category => {
+                return Err(format!(
+                    "Discord channel {} is not a Category channel, but a {:?} channel",
+                    channel.name, channel.kind
+                )
+                .into());
+            }
+            Channel::Guild(category) => category,
+            _ => return Err("Your Discord channel is weird".into()),
+        };
+
+        let bot = Self {
+            client,
+            http,
+            chat_category,
+            leaderboard_channel: env("DISCORD_LEADERBOARD_CHANNEL_ID")?,
+            moderator_channel: env("DISCORD_MODERATOR_CHANNEL_ID")?,
+            commands,
+        };
+        bot.commands.create(&bot.http, bot.guild_id()).await?;
+        Ok(bot)
+    }
+
+    fn guild_id(&self) -> GuildId {
+        self.chat_category.guild_id
+    }
+
+    /// Sets the tournament being processed, which languages chosen with the language command are
+    /// saved to. If there's none, they're saved to the database instead.
+    pub fn set_run_state(&self, run_state: Option<Arc<RunStateFile>>) {
+        *self.commands.run_state.write().unwrap() = run_state;
+    }
+
+    pub async fn send_moderator_message(&self, message: &str) -> Result<()> {
+        for message in split_message(message, 2000) {
+            self.moderator_channel
+                .send_message(&self.http, CreateMessage::new().content(message))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the leaderboard with the one for `new_db`, showing how it changed from
+    /// `old_players`
+    pub async fn update_leaderboard(
+        &self,
+        old_players: &SwitzerlandPlayerMap,
+        new_db: &Database,
+        player_id_to_discord_id: &HashMap<PlayerId, UserId>,
+    ) -> Result<()> {
+        let old_leaderboard_messages = self
+            .leaderboard_channel
+            .messages_iter(self.http.http())
+            .try_collect::<Vec<_>>()
+            .await?;
+        for message in
+            generate_leaderboard_messages(old_players, new_db, player_id_to_discord_id, 2000)
+        {
+            self.leaderboard_channel
+                .send_message(
+                    &self.http,
+                    CreateMessage::new()
+                        .content(message)
+                        .flags(MessageFlags::SUPPRESS_NOTIFICATIONS),
+                )
+                .await?;
+        }
+        let allow_bulk_delete_timestamp =
+            Timestamp::from_unix_timestamp(Timestamp::now().unix_timestamp() - 60 * 60 * 24 * 13)
+                .unwrap();
+        for messages in old_leaderboard_messages
+            .into_iter()
+            .chunks(100)
+            .into_iter()
+            .map(Itertools::collect_vec)
+        {
+            if messages
+                .iter()
+                .all(|x| x.timestamp > allow_bulk_delete_timestamp)
+            {
+                self.leaderboard_channel
+                    .delete_messages(self.http.http(), messages)
+                    .await?;
+            } else {
+                for message in messages {
+                    message.delete(&self.http).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the bot's commands and disconnects from Discord
+    pub async fn shutdown(self) -> Result<()> {
+        self.commands.delete(&self.http, self.guild_id()).await?;
+        self.client.shard_manager.shutdown_all().await;
+        Ok(())
+    }
+}
+
+/// Sends progress to players in their own channels under a category, and summaries to the
+/// moderator and leaderboard channels
+pub struct DiscordOutput<'a> {
+    bot: &'a DiscordBot,
+    channels: DiscordChannelsMap,
+    run_state: Arc<RunStateFile>,
+    notifier: ProgressNotifier,
+}
+
+impl<'a> DiscordOutput<'a> {
+    /// Processes a tournament with `bot`. Progress is sent to the players' channels along with any
+    /// other sinks in `notifier`.
+    pub fn new(
+        bot: &'a DiscordBot,
+        run_state: Arc<RunStateFile>,
+        notifier: ProgressNotifier,
+    ) -> Self {
+        bot.set_run_state(Some(run_state.clone()));
+        Self {
+            bot,
+            channels: DiscordChannelsMap::new(),
+            run_state,
+            notifier,
+        }
+    }
+
+    /// The Discord IDs of every player who checked in, by their player ID
+    fn player_discord_ids(teams: &TeamsMap) -> HashMap<PlayerId, UserId> {
+        teams
+            .values()
+            .filter(|team| !team.check_ins.is_empty())
+            .flat_map(|team| &team.members)
+            .map(|player| (PlayerId::Sendou(player.user_id), player.discord_id))
+            .collect()
+    }
+}
+
+impl TournamentOutput for DiscordOutput<'_> {
+    async fn create_channels(
+        &mut self,
+        get_tournament: &impl GetTournamentFn,
+        players: &mut SwitzerlandPlayerMap,
+    ) -> Result<()> {
+        let language_command_id = self
+            .bot
+            .commands
+            .language_command
+            .read()
+            .unwrap()
+            .ok_or("The language command hasn't been created")?;
+        let guild_channels = self
+            .bot
+            .guild_id()
+            .to_guild_cached(self.bot.http.cache())
+            .ok_or("Chat category Discord is not accessible by bot")?
+            .channels
+            .values()
+            .map(|channel| (channel.name.clone(), channel.id))
+            .collect();
+        self.channels = create_discord_channels(
+            &self.bot.http,
+            self.bot.guild_id(),
+            guild_channels,
+            self.bot.chat_category.id,
+            language_command_id,
+            &self.run_state.read(|state| state.channels.clone()),
+            get_tournament,
+            players,
+        )
+        .await?;
+        self.run_state
+            .update(|state| state.channels = self.channels.clone())?;
+        self.notifier.add_sink(DiscordSink {
+            http: self.bot.http.clone(),
+            channels: self.channels.clone(),
+        });
+        Ok(())
+    }
+
+    fn send_progress(&self, update: ProgressUpdate) -> Result<()> {
+        self.notifier.send(update)
+    }
+
+    fn players_updated(&self, players: &SwitzerlandPlayerMap) -> Result<()> {
+        self.notifier.players_updated(players)
+    }
+
+    async fn send_summary(
+        &self,
+        old_players: &SwitzerlandPlayerMap,
+        teams: &TeamsMap<'_>,
+        new_db: &Database,
+        get_tournament: &impl GetTournamentFn,
+    ) -> Result<()> {
+        println!("\nSending comparison to Discord...");
+        let tournament = get_tournament().await?;
+        let player_id_to_discord_id = Self::player_discord_ids(teams);
+
+        let mut players_in_discord = HashSet::new();
+        for user_id in player_id_to_discord_id.values().copied() {
+            if self
+                .bot
+                .guild_id()
+                .member(&self.bot.http, user_id)
+                .await
+                .is_ok()
+            {
+                players_in_discord.insert(user_id);
+            }
+        }
+
+        let mut message = String::new();
+        let _ = writeln!(
+            message,
+            "And that concludes {}! Thank you all for participating, and I hope you had a good time.",
+            tournament.context.name
+        );
+        format_results(&mut message, &tournament, |player| {
+            players_in_discord
+                .contains(&player.discord_id)
+                .then(|| player.discord_id.mention().to_string())
+        });
+
+        let _ = writeln!(message, "## Switzerland Power changes");
+        let show_placement_count = show_placement_count(new_db.players.len());
+        let should_show_rank = |player: &SwitzerlandPlayer| {
+            player
+                .rank
+                .is_some_and(|r| r.get() as usize <= show_placement_count)
+        };
+        for new_player in &new_db.players {
+            if new_player.rating.deviation > new_db.config.maximum_calced_rd {
+                continue;
+            }
+            let Some(discord_id) = player_id_to_discord_id.get(&new_player.id) else {
+                continue;
+            };
+            if !players_in_discord.contains(discord_id) {
+                continue;
+            }
+            let old_result = old_players.get(&new_player.id);
+            if let Some(old_result) = old_result
+                && old_result.rating == new_player.rating
+            {
+                continue;
+            }
+            let _ = writeln!(
+                message,
+                "- {} {}",
+                discord_id.mention(),
+                format_player_rank_summary(
+                    old_result,
+                    new_player,
+                    old_result.is_some_and(should_show_rank) || should_show_rank(new_player),
+                    false,
+                )
+            );
+        }
+
+        self.bot.send_moderator_message(&message).await
+    }
+
+    async fn update_leaderboard(
+        &self,
+        old_players: &SwitzerlandPlayerMap,
+        teams: &TeamsMap<'_>,
+        new_db: &Database,
+    ) -> Result<()> {
+        // Players will look up their new ratings as soon as they see the leaderboard
+        self.bot
+            .commands
+            .player_commands
+            .set_database(new_db.clone());
+        self.bot
+            .update_leaderboard(old_players, new_db, &Self::player_discord_ids(teams))
+            .await
+    }
+
+    fn user_languages(&self) -> HashMap<UserId, Language> {
+        self.run_state.read(|state| state.languages.clone())
+    }
+
+    async fn finish(self) -> Result<()> {
+        self.notifier.wait_for_deliveries().await;
+        match &self.bot.commands.moderator_commands {
+            Some(moderator_commands) => {
+                let cleanup = moderator_commands.wait_for_cleanup();
+                self.bot
+                    .send_moderator_message(&format!(
+                        "Tournament {} has been processed. Use {} when finished to clean up the player channels.",
+                        self.run_state.read(|state| state.tournament_id),
+                        moderator_commands.cleanup_command_display(),
+                    ))
+                    .await?;
+                let _ = cleanup.await;
+            }
+            None => {
+                println!("Press enter when finished to clean up Discord channels");
+                let _ = io::stdin().read(&mut [0]);
+            }
+        }
+        clean_up_discord_channels(&self.bot.http, self.channels.into_values()).await;
+        Ok(())
+    }
+}
+
+/// Sends progress to the channels of each team
+struct DiscordSink {
+    http: DiscordHttp,
+    channels: DiscordChannelsMap,
+}
+
+#[serenity::async_trait]
+impl NotificationSink for DiscordSink {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    async fn notify(&self, notification: &ProgressNotification) -> Result<Option<MessageId>> {
+        let Some(discord_channel) = self.channels.get(&notification.team_id) else {
+            return Ok(None);
+        };
+        let mut message = format_link(&notification.text, &format!("<{}>", notification.link));
+        if notification.team_size > 1 {
+            message = format!("{} {message}", notification.discord_id.mention());
+        }
+        // discord_channel
+        //     .create_permission(
+        //         self.http.http(),
+        //         PermissionOverwrite {
+        //             allow: USER_CHANNEL_PERMS,
+        //             deny: Permissions::empty(),
+        //             kind: PermissionOverwriteType::Member(notification.discord_id),
+        //         },
+        //     )
+        //     .await?;
+        let send_result = discord_channel
+            .send_message(
+                &self.http,
+                CreateMessage::new()
+                    .content(message)
+                    .add_file(CreateAttachment::bytes(
+                        notification.animation.to_vec(),
+                        &notification.animation_file,
+                    )),
+            )
+            .await;
+        match send_result {
+            Ok(message) => Ok(Some(message.id)),
+            Err(result) => {
+                if let Ok(backups_dir) = env::<PathBuf>("GENERATED_ANIM_BACKUPS_DIR")
+                    && let Err(err) = fs::write(
+                        backups_dir.join(&notification.animation_file),
+                        &notification.animation,
+                    )
+                {
+                    println!(
+                        "Failed to save backup animation file for {}: {err}",
+                        notification.set_id
+                    );
+                }
+                Err(result.into())
+            }
+        }
+    }
+}
+
+/// Creates a command with its name and description translated into every language that has them
+pub fn localized_command(
+    name: impl Fn(&Language) -> Cow<'static, str>,
+    desc: impl Fn(&Language) -> Cow<'static, str>,
+) -> CreateCommand {
+    let default_language = Language::default();
+    let mut command =
+        CreateCommand::new(name(&default_language)).description(desc(&default_language));
+    for language in Language::supported_languages() {
+        if let Some(discord_lang_id) = language.discord_id()
+            && let Some(fallback_language) = language.fallback()
+        {
+            let localized_name = name(language);
+            if localized_name != name(&fallback_language) {
+                command = command.name_localized(discord_lang_id, localized_name);
+            }
+
+            let localized_desc = desc(language);
+            if localized_desc != desc(&fallback_language) {
+                command = command.description_localized(discord_lang_id, localized_desc);
+            }
+        }
+    }
+    command
+}
+
+/// Creates a command option with its name and description translated into every language that
+/// has them
+pub fn localized_option(
+    kind: CommandOptionType,
+    name: impl Fn(&Language) -> Cow<'static, str>,
+    desc: impl Fn(&Language) -> Cow<'static, str>,
+) -> CreateCommandOption {
+    let default_language = Language::default();
+    let mut option =
+        CreateCommandOption::new(kind, name(&default_language), desc(&default_language));
+    for language in Language::supported_languages() {
+        if let Some(discord_lang_id) = language.discord_id()
+            && let Some(fallback_language) = language.fallback()
+        {
+            let localized_name = name(language);
+            if localized_name != name(&fallback_language) {
+                option = option.name_localized(discord_lang_id, localized_name);
+            }
+
+            let localized_desc = desc(language);
+            if localized_desc != desc(&fallback_language) {
+                option = option.description_localized(discord_lang_id, localized_desc);
+            }
+        }
+    }
+    option
+}
+
+fn create_language_command() -> CreateCommand {
+    let mut option = localized_option(
+        CommandOptionType::String,
+        Language::language_command_name,
+        Language::language_command_arg_desc,
+    );
+    for language in Language::supported_languages() {
+        option = option.add_string_choice(language.name(), language.id());
+    }
+    localized_command(
+        Language::language_command_name,
+        Language::language_command_desc,
+    )
+    .add_option(option)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn create_discord_channels(
+    discord_http: &DiscordHttp,
+    guild_id: GuildId,
+    mut guild_channels_by_name: HashMap<String, ChannelId>,
+    category: ChannelId,
+    language_command_id: CommandId,
+    resumed_channels: &DiscordChannelsMap,
+    get_tournament: &impl GetTournamentFn,
+    players: &mut SwitzerlandPlayerMap,
+) -> Result<DiscordChannelsMap> {
+    println!("Creating Discord channels...");
+
+    let mut channels = HashMap::new();
+
+    let me_user = discord_http.cache().current_user();
+    let commentators_role = env("DISCORD_COMMENTATORS_ROLE_ID")?;
+
+    for team in get_tournament().await?.context.teams {
+        if team.check_ins.is_empty() {
+            continue;
+        }
+
+        let mut members = vec![];
+        for player in &team.members {
+            let switzerland_player = players.get_mut(&PlayerId::Sendou(player.user_id)).unwrap();
+            let guess_language = switzerland_player.language.is_none();
+            let language = *switzerland_player.language.get_or_insert_with(|| {
+                player
+                    .country
+                    .as_ref()
+                    .and_then(|lang| Language::guess_from_country(lang))
+                    .unwrap_or_default()
+            });
+            let user = player.discord_id.to_user(discord_http).await?;
+            members.push((user, language, guess_language));
+        }
+
+        if let Some(&channel) = resumed_channels.get(&team.id)
+            && guild_channels_by_name.values().any(|&x| x == channel)
+        {
+            channels.insert(team.id, channel);
+            continue;
+        }
+
+        let channel_name = match members.as_slice() {
+            [] => continue,
+            [(user, _, _)] => format!("switzerland-{}", user.name.replace('.', "")),
+            _ => format!(
+                "switzerland-{}",
+                team.name
+                    .to_lowercase()
+                    .split_whitespace()
+                    .join("-")
+                    .replace('.', "")
+            ),
+        };
+        let channel = if let Some(channel) = guild_channels_by_name.remove(&channel_name) {
+            channel
+                .say(discord_http, members[0].1.bot_crashed())
+                .await?;
+            channel
+        } else {
+            let permissions = [PermissionOverwrite {
+                allow: Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES,
+                deny: Permissions::empty(),
+                kind: PermissionOverwriteType::Member(me_user.id),
+            }]
+            .into_iter()
+            .chain(members.iter().map(|(user, _, _)| PermissionOverwrite {
+                allow: USER_CHANNEL_PERMS,
+                deny: Permissions::empty(),
+                kind: PermissionOverwriteType::Member(user.id),
+            }))
+            .chain([
+                PermissionOverwrite {
+                    allow: Permissions::VIEW_CHANNEL,
+                    deny: Permissions::SEND_MESSAGES,
+                    kind: PermissionOverwriteType::Role(commentators_role),
+                },
+                PermissionOverwrite {
+                    allow: Permissions::empty(),
+                    deny: Permissions::VIEW_CHANNEL,
+                    kind: PermissionOverwriteType::Role(guild_id.everyone_role()),
+                },
+            ]);
+            let channel = guild_id
+                .create_channel(
+                    discord_http,
+                    CreateChannel::new(channel_name)
+                        .category(category)
+                        .permissions(permissions),
+                )
+                .await?;
+            for (user, language, _) in &members {
+                channel
+                    .say(discord_http, language.channel_explanation(user.mention()))
+                    .await?;
+            }
+            channel.id
+        };
+        let mut explained_languages = vec![];
+        for (_, language, guess_language) in &members {
+            if *guess_language && !explained_languages.contains(language) {
+                explained_languages.push(*language);
+                let language_command =
+                    CommandIdDisplay(language.language_command_name(), language_command_id);
+                channel
+                    .say(
+                        discord_http,
+                        language.language_command_explanation(&language_command, *language),
+                    )
+                    .await?;
+            }
+        }
+        channels.insert(team.id, channel);
+    }
+
+    Ok(channels)
+}
+
+async fn clean_up_discord_channels(
+    http: &DiscordHttp,
+    channels: impl IntoIterator<Item = ChannelId>,
+) {
+    println!("Deleting Discord channels...");
+    for channel in channels {
+        let _ = channel.delete(http.http()).await;
+    }
+}
@@ -301,7 +301,61 @@ language_messages! {
     changed_language(language: Language) => {
         Language::EnglishUnitedStates => "Bot language changed to {language}",
     },
+    language_not_saved => {
+        Language::EnglishUnitedStates => "Couldn't save your language. Make sure your Discord account is linked on sendou.ink, or try again once you've played in a Switzerland tournament.",
+    },
     round_played(win_lose: &str, against: &str) => {
         Language::EnglishUnitedStates => "{win_lose} vs {against}",
     },
+    sp_command_name => {
+        Language::EnglishUnitedStates => "sp",
+    },
+    sp_command_desc => {
+        Language::EnglishUnitedStates => "Shows a player's Switzerland Power",
+    },
+    rank_command_name => {
+        Language::EnglishUnitedStates => "rank",
+    },
+    rank_command_desc => {
+        Language::EnglishUnitedStates => "Shows a player's rank on the Switzerland Power leaderboard",
+    },
+    history_command_name => {
+        Language::EnglishUnitedStates => "history",
+    },
+    history_command_desc => {
+        Language::EnglishUnitedStates => "Shows how a player's Switzerland Power changed in their last tournaments",
+    },
+    player_arg_name => {
+        Language::EnglishUnitedStates => "player",
+    },
+    player_arg_desc => {
+        Language::EnglishUnitedStates => "The name or sendou.ink ID of the player to look up. If not specified, you are looked up",
+    },
+    count_arg_name => {
+        Language::EnglishUnitedStates => "count",
+    },
+    count_arg_desc => {
+        Language::EnglishUnitedStates => "The number of tournaments to show",
+    },
+    player_not_found => {
+        Language::EnglishUnitedStates => "Couldn't find your Switzerland Power. Make sure your Discord account is linked on sendou.ink, or look up a player by name.",
+    },
+    player_sp(player: &str, sp: &str) => {
+        Language::EnglishUnitedStates => "{player} has {sp}",
+    },
+    player_calculating(player: &str, percent: f64) => {
+        Language::EnglishUnitedStates => "{player}'s Switzerland Power is still being calculated ({percent:.0}% complete)",
+    },
+    player_rank(player: &str, rank: u32, total: usize) => {
+        Language::EnglishUnitedStates => "{player} is ranked #{rank} of {total}",
+    },
+    player_unranked(player: &str) => {
+        Language::EnglishUnitedStates => "{player} isn't ranked yet",
+    },
+    history_header(player: &str) => {
+        Language::EnglishUnitedStates => "{player}'s last tournaments:",
+    },
+    history_empty(player: &str) => {
+        Language::EnglishUnitedStates => "{player} hasn't played in any tournaments yet",
+    },
 }
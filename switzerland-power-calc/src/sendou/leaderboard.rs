@@ -1,7 +1,7 @@
 use crate::counts::leaderboard_count;
 use crate::db::{Database, PlayerId, SwitzerlandPlayerMap};
 use crate::format_sp;
-use crate::sendou::{env_str, format_link};
+use crate::sendou::{env_str, format_link, split_message};
 use serenity::all::{Mentionable, UserId};
 use std::cmp::Ordering;
 use std::collections::HashMap;
@@ -14,7 +14,6 @@ pub fn generate_leaderboard_messages(
 ) -> Vec<String> {
     let leaderboard_count = leaderboard_count(new_db.players.len());
 
-    let mut messages = Vec::new();
     let mut message = format!("# Switzerland Top {leaderboard_count}");
 
     let get_arrow = |var, default: &str| {
@@ -61,16 +60,9 @@ pub fn generate_leaderboard_messages(
                 .unwrap_or_default(),
             format_sp(player.rating, false),
         );
-        if message.len() + line.len() >= max_message_len {
-            messages.push(message.clone());
-            message.clear();
-        }
-        if !message.is_empty() {
-            message.push('\n');
-        }
+        message.push('\n');
         message.push_str(&line);
     }
 
-    messages.push(message);
-    messages
+    split_message(&message, max_message_len)
 }
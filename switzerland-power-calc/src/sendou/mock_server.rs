@@ -0,0 +1,217 @@
+use crate::sendou::api::SendouClient;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub const TOURNAMENT_FIXTURE: &[u8] = include_bytes!("../../test-fixtures/register.data.json");
+pub const MATCH_FIXTURE: &[u8] = include_bytes!("../../test-fixtures/match.data.json");
+pub const USER_FIXTURE: &[u8] = include_bytes!("../../test-fixtures/user.data.json");
+
+/// A path in the temp directory that's unique to this test run
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("spc-{name}-test-{}", std::process::id()))
+}
+
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    pub method: String,
+    /// The path of the request, including the query string
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    /// Responses that are returned once each, before falling back to `routes`
+    queued: HashMap<String, VecDeque<MockResponse>>,
+    routes: HashMap<String, MockResponse>,
+    requests: Vec<MockRequest>,
+}
+
+/// A local stand-in for sendou.ink that serves canned responses and records every request made to
+/// it. Unknown paths respond with 404.
+pub struct MockSendouServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockSendouServer {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let server_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = server_state.clone();
+                thread::spawn(move || handle_connection(stream, &state));
+            }
+        });
+        Self { addr, state }
+    }
+
+    /// Serves tournament 1234 from `test-fixtures`, along with the results of each of its sets,
+    /// user `alpha`, and the seeding endpoint
+    pub fn with_fixtures() -> Self {
+        let server = Self::start();
+        server.route(
+            "/to/1234/register.data?_routes=features/tournament/routes/to.$id",
+            MockResponse::ok(TOURNAMENT_FIXTURE),
+        );
+        for match_id in [11, 12, 21, 22] {
+            server.route(
+                &format!(
+                    "/to/1234/matches/{match_id}.data?_routes=features/tournament-match/routes/to.$id.matches.$mid"
+                ),
+                MockResponse::ok(MATCH_FIXTURE),
+            );
+        }
+        server.route("/u/alpha.data", MockResponse::ok(USER_FIXTURE));
+        server.route("/api/tournament/1234/seeds", MockResponse::status(200));
+        server
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// A client for this server that retries without waiting
+    pub fn client(&self) -> SendouClient {
+        SendouClient::new(&self.base_url())
+            .unwrap()
+            .with_retry_delay(Duration::from_millis(1))
+    }
+
+    /// Sets the response returned whenever `path` is requested
+    pub fn route(&self, path: &str, response: MockResponse) {
+        let mut state = self.state.lock().unwrap();
+        state.routes.insert(path.to_string(), response);
+    }
+
+    /// Returns `response` the next time `path` is requested, taking priority over [`Self::route`]
+    pub fn queue(&self, path: &str, response: MockResponse) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .queued
+            .entry(path.to_string())
+            .or_default()
+            .push_back(response);
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn requested_paths(&self) -> Vec<String> {
+        self.requests().into_iter().map(|x| x.path).collect()
+    }
+}
+
+fn handle_connection(stream: TcpStream, state: &Mutex<MockState>) {
+    let Some(request) = read_request(&stream) else {
+        return;
+    };
+    let response = {
+        let mut state = state.lock().unwrap();
+        let response = state
+            .queued
+            .get_mut(&request.path)
+            .and_then(|queue| queue.pop_front())
+            .or_else(|| state.routes.get(&request.path).cloned())
+            .unwrap_or_else(|| MockResponse::status(404));
+        state.requests.push(request);
+        response
+    };
+    let mut head = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    let mut stream = stream;
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(&response.body);
+}
+
+fn read_request(stream: &TcpStream) -> Option<MockRequest> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut request_line = line.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+
+    let mut headers = vec![];
+    loop {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (key, value) = header.split_once(':')?;
+        headers.push((key.trim().to_string(), value.trim().to_string()));
+    }
+
+    let content_length = headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(MockRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
@@ -1,238 +1,190 @@
+pub mod api;
+mod bot;
 mod cli_helpers;
 mod discord;
 pub mod lang;
 pub mod leaderboard;
+#[cfg(test)]
+mod mock_server;
+mod notify;
+mod output;
+mod overlay;
+mod player_commands;
+mod polling;
 mod rank_set;
+mod recorder;
+mod replay;
+mod run_state;
 pub mod schema;
+mod standings;
 pub mod turbo_stream;
 mod types;
 
-use crate::db::{Database, PlayerId, SwitzerlandPlayer, SwitzerlandPlayerMap};
-use crate::sendou::discord::{DiscordEventHandler, DiscordHttp};
-use crate::sendou::lang::{CommandIdDisplay, Language};
+use crate::db::{
+    Database, PlayerId, RatingHistoryEntry, SetRecord, SwitzerlandPlayer, SwitzerlandPlayerMap,
+    TournamentParticipant, TournamentRecord,
+};
+use crate::sendou::api::SendouClient;
+use crate::sendou::discord::{DiscordBot, DiscordOutput};
+use crate::sendou::output::{ConsoleOutput, ProgressUpdate, TournamentOutput};
+use crate::sendou::player_commands::PlayerCommands;
+use crate::sendou::polling::{PollSchedule, TournamentPoller};
 use crate::sendou::schema::{
-    ToMatchResponse, ToResponse, Tournament, TournamentContext, TournamentData, TournamentMatch,
+    MatchResult, ToMatchResponse, Tournament, TournamentContext, TournamentData,
     TournamentMatchOpponent, TournamentMatchResult, TournamentMatchStatus,
-    TournamentRoundMapsMatchType, TournamentStageSettings, TournamentTeam,
-};
-use crate::sendou::types::{DiscordChannelsMap, GetTournamentFn, TeamsMap};
-use crate::{
-    Error, MAXIMUM_CALCED_RD, Result, format_player_rank_summary, format_player_simply, format_sp,
-    summarize_differences,
+    TournamentRoundMapsMatchType, TournamentStageSettings, TournamentTeam, TournamentTeamMember,
 };
+use crate::sendou::types::{PollTournamentFn, TeamsMap};
+use crate::{Result, format_player_simply, format_sp, rating, summarize_differences};
 use chrono::Utc;
-use dashmap::DashMap;
 use itertools::Itertools;
-use reqwest::{Client as ReqwestClient, Client};
 use rustyline_async::{Readline, ReadlineError, ReadlineEvent, SharedWriter};
-use serde_json::json;
-use serenity::FutureExt;
-use serenity::all::{
-    ActivityData, CacheHttp, Channel, ChannelId, ChannelType, CommandId, CommandOptionType,
-    CreateAttachment, CreateChannel, CreateCommand, CreateCommandOption, CreateMessage,
-    GatewayIntents, Guild, GuildId, Mentionable, MessageFlags, PermissionOverwrite,
-    PermissionOverwriteType, Permissions, UserId,
-};
-use serenity::futures::TryStreamExt;
-use serenity::model::Timestamp;
-use skillratings::Outcomes;
-use skillratings::glicko2::{Glicko2Config, Glicko2Rating, decay_deviation, glicko2};
 use std::collections::{HashMap, HashSet};
 use std::fmt::Write as FmtWrite;
-use std::io::{Read, Write as IoWrite};
+use std::io::Write as IoWrite;
+use std::mem;
 use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::str::FromStr;
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::Arc;
 use std::time::Duration;
 use std::{fs, io};
-use switzerland_power_animated::{AsyncAnimationGenerator, MatchOutcome, PowerStatus};
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use tokio::sync::oneshot;
-use tokio::time;
-use tokio::time::{Interval, MissedTickBehavior, sleep};
+use tokio::time::sleep;
 use unic_emoji_char::is_emoji_presentation;
 
+use crate::config::SeasonConfig;
 use crate::counts::{leaderboard_count, show_placement_count};
 use crate::error::ErrorKind;
 pub use crate::migration::migration_cli;
 use crate::sendou::cli_helpers::print_seeding_instructions;
-use crate::sendou::leaderboard::generate_leaderboard_messages;
 use crate::sendou::rank_set::RankVec;
+use crate::sendou::recorder::{SnapshotRecorder, latest_snapshot};
+use crate::sendou::run_state::{RunStateFile, progress_message_prefix};
+use crate::sendou::standings::{Standing, compute_standings};
 use crate::sendou::turbo_stream::TurboStreamed;
+pub use bot::bot_cli;
+pub use notify::SinkOptions;
+pub use replay::replay_cli;
 pub use schema::SendouId;
 
-const POLL_TIME: Duration = Duration::from_secs(10);
-const USER_CHANNEL_PERMS: Permissions = Permissions::VIEW_CHANNEL
-    .union(Permissions::SEND_MESSAGES)
-    .union(Permissions::USE_APPLICATION_COMMANDS);
-
 #[tokio::main]
-pub async fn sendou_cli(in_db: &Path, out_db: &Path, tournament_id: SendouId) -> Result<()> {
+#[allow(clippy::too_many_arguments)]
+pub async fn sendou_cli(
+    in_db: &Path,
+    out_db: &Path,
+    tournament_id: SendouId,
+    record_dir: Option<&Path>,
+    diagnostics_dir: Option<&Path>,
+    state_path: &Path,
+    headless: bool,
+    sinks: SinkOptions<'_>,
+) -> Result<()> {
     if let Some(parent) = out_db.parent() {
         fs::create_dir_all(parent)?;
     }
-
-    let tournament_url = format!(
-        "https://sendou.ink/to/{tournament_id}/register.data?_routes=features/tournament/routes/to.$id"
-    );
-    let http_client = reqwest::ClientBuilder::new()
-        .user_agent(concat!(
-            env!("CARGO_PKG_NAME"),
-            " (https://github.com/Gaming32/switzerland-power-calc, ",
-            env!("CARGO_PKG_VERSION"),
-            ")"
-        ))
-        .build()?;
-
-    let discord_user_languages = Arc::new(DashMap::new());
-
-    let (discord_ready_send, discord_ready) = oneshot::channel();
-    let language_command_lock = Arc::new(RwLock::new(None));
-    let discord_client = serenity::client::ClientBuilder::new(
-        env_str("DISCORD_BOT_TOKEN")?,
-        GatewayIntents::GUILDS | GatewayIntents::GUILD_MEMBERS,
-    )
-    .event_handler(DiscordEventHandler {
-        ready: Mutex::new(Some(discord_ready_send)),
-        language_command: language_command_lock.clone(),
-        language_output: discord_user_languages.clone(),
-    })
-    .activity(ActivityData::competing("Switzerland"))
-    .await?;
-    discord_client.shard_manager.set_shards(0, 1, 1).await;
-    discord_client.shard_manager.initialize()?;
-    discord_ready.await.unwrap();
-    let discord_http = DiscordHttp::new(discord_client.cache.clone(), discord_client.http.clone());
-
-    let chat_category = match env::<ChannelId>("DISCORD_CHAT_CATEGORY_ID")?
-        .to_channel(&discord_http)
-        .await?
-    {
-        Channel::Private(channel) => {
-            return Err(
-                format!("Discord channel {} is not part of a guild", channel.name()).into(),
-            );
-        }
-        Channel::Guild(channel) if channel.kind != ChannelType::Category => {
-            return Err(format!(
-                "Discord channel {} is not a Category channel, but a {:?} channel",
-                channel.name, channel.kind
-            )
-            .into());
-        }
-        Channel::Guild(category) => category,
-        _ => return Err("Your Discord channel is weird".into()),
-    };
-    let get_guild = || -> Result<_> {
-        chat_category
-            .guild_id
-            .to_guild_cached(discord_http.cache())
-            .ok_or_else(|| "Chat category Discord is not accessible by bot".into())
-    };
-    let leaderboard_channel = env::<ChannelId>("DISCORD_LEADERBOARD_CHANNEL_ID")?;
-    let moderator_channel = env::<ChannelId>("DISCORD_MODERATOR_CHANNEL_ID")?;
-
-    let get_tournament = async || -> Result<_> {
-        let real_get = async || {
-            Ok(http_client
-                .get(&tournament_url)
-                .send()
-                .await?
-                .error_for_status()?
-                .json::<TurboStreamed<ToResponse>>()
-                .await?
-                .0
-                .to
-                .data
-                .tournament)
+    let recorder = record_dir
+        .map(SnapshotRecorder::new)
+        .transpose()?
+        .map(Arc::new);
+    let diagnostics = diagnostics_dir
+        .map(SnapshotRecorder::new)
+        .transpose()?
+        .map(Arc::new);
+
+    let sendou = SendouClient::from_env()?
+        .with_recorder(recorder)
+        .with_diagnostics(diagnostics);
+    let run_state = Arc::new(RunStateFile::load(state_path, tournament_id)?);
+
+    if headless {
+        let notifier = if !sinks.is_empty() {
+            Some(sinks.create_notifier(run_state.clone()).await?)
+        } else {
+            None
         };
-        for i in 1..=4 {
-            let result = real_get().await;
-            match &result {
-                Ok(_) => return result,
-                Err(Error {
-                    error: ErrorKind::Http(http),
-                    ..
-                }) if http.is_decode() => return result,
-                _ => {}
-            }
-            sleep(Duration::from_secs(1 << i)).await;
-        }
-        real_get().await
-    };
+        run_live_tournament(
+            ConsoleOutput::new(notifier),
+            in_db,
+            out_db,
+            &sendou,
+            tournament_id,
+            &run_state,
+        )
+        .await
+    } else {
+        let notifier = sinks.create_notifier(run_state.clone()).await?;
+        let player_commands = Arc::new(PlayerCommands::new(Database::read(in_db)?, sendou.clone()));
+        let bot = DiscordBot::connect(player_commands, None).await?;
+        run_live_tournament(
+            DiscordOutput::new(&bot, run_state.clone(), notifier),
+            in_db,
+            out_db,
+            &sendou,
+            tournament_id,
+            &run_state,
+        )
+        .await?;
+        bot.shutdown().await
+    }
+}
+
+async fn run_live_tournament(
+    mut output: impl TournamentOutput,
+    in_db: &Path,
+    out_db: &Path,
+    sendou: &SendouClient,
+    tournament_id: SendouId,
+    run_state: &Arc<RunStateFile>,
+) -> Result<()> {
+    let poller = TournamentPoller::new(sendou, tournament_id);
+    let poll_tournament = async || poller.poll().await;
+    let get_tournament = async || poller.latest().await;
     let initial_tournament = get_tournament().await?;
 
-    let old_players = Database::read(in_db)?.into_map();
+    let old_db = Database::read(in_db)?;
+    let config = old_db.config;
+    let old_players = old_db.clone().into_map();
     let mut new_players = old_players.clone();
 
-    let teams = initialize_teams(&initial_tournament, &mut new_players, &http_client).await?;
-    wait_for_tournament_start(&initial_tournament.context, &get_tournament).await?;
+    let (teams, mut record) =
+        initialize_teams(&config, &initial_tournament, &mut new_players, Some(sendou)).await?;
+    wait_for_tournament_start(&initial_tournament.context, &poll_tournament).await?;
 
-    let language_command = create_language_command();
-    let language_command_id = get_guild()?
-        .create_command(&discord_http, language_command)
-        .await?
-        .id;
-    *language_command_lock.write().unwrap() = Some(language_command_id);
-    drop(language_command_lock);
-
-    let guild_channels = get_guild()?
-        .channels
-        .values()
-        .map(|channel| (channel.name.clone(), channel.id))
-        .collect();
-    let discord_channels = create_discord_channels(
-        &discord_http,
-        chat_category.guild_id,
-        guild_channels,
-        chat_category.id,
-        language_command_id,
-        &get_tournament,
-        &mut new_players,
-    )
-    .await?;
+    output
+        .create_channels(&get_tournament, &mut new_players)
+        .await?;
 
     run_tournament(
-        &http_client,
-        &discord_http,
+        &MatchResultsSource::Sendou(sendou.clone()),
+        &output,
         &mut new_players,
         &teams,
-        &discord_user_languages,
-        &discord_channels,
-        &get_tournament,
-    )
-    .await?;
-
-    let new_db = finalize_tournament(out_db, &old_players, new_players)?;
-    send_summaries_to_discord(
-        &discord_http,
-        &*get_guild()?,
-        moderator_channel,
-        leaderboard_channel,
-        &old_players,
-        &teams,
-        &new_db,
-        &get_tournament,
+        &mut record,
+        &config,
+        run_state,
+        &poll_tournament,
+        true,
     )
     .await?;
 
-    println!("Press enter when finished to clean up Discord channels");
-    let _ = io::stdin().read(&mut [0]);
-    clean_up_discord_channels(&discord_http, discord_channels.into_values()).await;
-
-    get_guild()?
-        .delete_command(discord_http.http(), language_command_id)
+    let new_db = finalize_tournament(out_db, &old_db, &old_players, new_players, record)?;
+    output
+        .send_summary(&old_players, &teams, &new_db, &get_tournament)
+        .await?;
+    output
+        .update_leaderboard(&old_players, &teams, &new_db)
         .await?;
-    discord_client.shard_manager.shutdown_all().await;
+
+    let user_languages = output.user_languages();
+    output.finish().await?;
 
     let new_user_languages = teams
         .values()
-        .filter_map(|team| {
-            let player = team.members.first().unwrap();
-            discord_user_languages
+        .flat_map(|team| &team.members)
+        .filter_map(|player| {
+            user_languages
                 .get(&player.discord_id)
-                .as_deref()
                 .copied()
                 .map(|lang| (player.user_id, lang))
         })
@@ -249,6 +201,7 @@ pub async fn sendou_cli(in_db: &Path, out_db: &Path, tournament_id: SendouId) ->
         }
         new_db.write(out_db)?;
     }
+    run_state.remove()?;
 
     Ok(())
 }
@@ -267,60 +220,54 @@ where
 }
 
 async fn initialize_teams<'a>(
+    config: &SeasonConfig,
     tournament: &'a Tournament,
     players: &mut SwitzerlandPlayerMap,
-    http_client: &Client,
-) -> Result<TeamsMap<'a>> {
-    let mut teams = HashMap::new();
-    for player in players.values_mut() {
-        player.since_played += 1;
-    }
-    for team in &tournament.context.teams {
-        let player = team.members.first().expect("Sendou team has no members");
-        let starting_rating = team.avg_seeding_skill_ordinal.clamp(-10.0, 40.0);
-        teams.insert(team.id, team);
-        players
-            .entry(PlayerId::Sendou(player.user_id))
-            .and_modify(|player| {
-                // since_played will be 1 above the desired value due to the increment above
-                for _ in 1..player.since_played {
-                    player.rating = decay_deviation(&player.rating);
-                }
-                player.since_played = 0;
-            })
-            .or_insert_with(|| SwitzerlandPlayer {
-                id: PlayerId::Sendou(player.user_id),
-                rating: Glicko2Rating {
-                    rating: starting_rating * 10.0 + 1500.0,
-                    deviation: 350.0 - starting_rating.abs() * 3.75,
-                    ..Default::default()
-                },
-                unrated: true,
-                ..Default::default()
+    sendou: Option<&SendouClient>,
+) -> Result<(TeamsMap<'a>, TournamentRecord)> {
+    let teams: TeamsMap = tournament
+        .context
+        .teams
+        .iter()
+        .map(|team| (team.id, team))
+        .collect();
+    let record = TournamentRecord {
+        id: tournament.context.id,
+        date: tournament.context.start_time,
+        participants: tournament
+            .context
+            .teams
+            .iter()
+            .flat_map(|team| {
+                team.members.iter().map(|player| TournamentParticipant {
+                    player: PlayerId::Sendou(player.user_id),
+                    display_name: Some(player.username.clone()),
+                    seeding_skill_ordinal: team.avg_seeding_skill_ordinal,
+                })
             })
-            .display_name = Some(player.username.clone());
-    }
+            .collect(),
+        sets: vec![],
+    };
+    rating::start_tournament(config, players, &record.participants);
 
     let sorted_players = print_seeding_instructions(
-        players,
-        teams.values().map(|team| {
-            (
-                team,
-                PlayerId::Sendou(team.members.first().unwrap().user_id),
-            )
-        }),
+        teams
+            .values()
+            .filter_map(|team| Some((team, team_player(players, team)?))),
         |team, player| {
             format!(
                 "{} ({}) [{}{}]",
                 team.name,
-                team.members.first().unwrap().username,
+                team.members.iter().map(|x| &x.username).join(", "),
                 format_sp(player.rating, true),
                 if player.unrated { " (NEW)" } else { "" }
             )
         },
     );
 
-    if tournament.data.stages.is_empty() {
+    if tournament.data.stages.is_empty()
+        && let Some(sendou) = sendou
+    {
         let mut seeded_team_ids = vec![];
         let (above_1500, below_1500) = sorted_players.split_at(
             sorted_players
@@ -330,32 +277,42 @@ async fn initialize_teams<'a>(
         );
         seeded_team_ids.extend(above_1500.iter().map(|(t, _)| t.id));
         for team in &tournament.context.teams {
-            let player = &players[&PlayerId::Sendou(team.members.first().unwrap().user_id)];
-            if player.rating.rating == 1500.0 {
+            if team_player(players, team).is_some_and(|player| player.rating.rating == 1500.0) {
                 seeded_team_ids.push(team.id);
             }
         }
         seeded_team_ids.extend(below_1500.iter().map(|(t, _)| t.id));
-        http_client
-            .post(format!(
-                "https://sendou.ink/api/tournament/{}/seeds",
-                tournament.context.id
-            ))
-            .bearer_auth(env_str("SENDOU_WRITE_TOKEN")?)
-            .json(&json!({
-                "tournamentTeamIds": seeded_team_ids,
-            }))
-            .send()
-            .await?
-            .error_for_status()?;
+        sendou
+            .set_seeds(tournament.context.id, &seeded_team_ids)
+            .await?;
     }
 
-    Ok(teams)
+    Ok((teams, record))
+}
+
+/// A stand-in player with the combined rating of a team's members
+fn team_player(players: &SwitzerlandPlayerMap, team: &TournamentTeam) -> Option<SwitzerlandPlayer> {
+    let members = team
+        .player_ids()
+        .iter()
+        .map(|id| players.get(id))
+        .collect::<Option<Vec<_>>>()?;
+    if let [member] = members.as_slice() {
+        return Some((*member).clone());
+    }
+    Some(SwitzerlandPlayer {
+        id: members.first()?.id.clone(),
+        display_name: Some(team.name.clone()),
+        rating: rating::team_rating(&members.iter().map(|x| x.rating).collect_vec()),
+        unrated: members.iter().all(|x| x.unrated),
+        calced: members.iter().all(|x| x.calced),
+        ..Default::default()
+    })
 }
 
 async fn wait_for_tournament_start(
     tournament_context: &TournamentContext,
-    get_tournament: &impl GetTournamentFn,
+    poll_tournament: &impl PollTournamentFn,
 ) -> Result<()> {
     if let Ok(delay) = tournament_context
         .start_time
@@ -371,166 +328,98 @@ async fn wait_for_tournament_start(
     }
 
     println!("Waiting for tournament to be started...");
+    let mut schedule = PollSchedule::default();
     loop {
-        if !get_tournament().await?.data.stages.is_empty() {
+        let poll = poll_tournament().await?;
+        if !poll.tournament.data.stages.is_empty() {
             break;
         }
-        sleep(POLL_TIME).await;
+        let (delay, message) = schedule.next_delay_logged(&poll);
+        if let Some(message) = message {
+            println!("{message}");
+        }
+        sleep(delay).await;
     }
 
     Ok(())
 }
 
-fn create_language_command() -> CreateCommand {
-    let default_language = Language::default();
-    let base_command_name = default_language.language_command_name();
-    let base_command_desc = default_language.language_command_desc();
-    let base_command_arg_desc = default_language.language_command_arg_desc();
-
-    let mut command =
-        CreateCommand::new(base_command_name.clone()).description(base_command_desc.clone());
-    let mut option = CreateCommandOption::new(
-        CommandOptionType::String,
-        base_command_name.clone(),
-        base_command_arg_desc.clone(),
-    );
-
-    for language in Language::supported_languages() {
-        if let Some(discord_lang_id) = language.discord_id()
-            && let Some(fallback_language) = language.fallback()
-        {
-            let localized_name = language.language_command_name();
-            if localized_name != fallback_language.language_command_name() {
-                command = command.name_localized(discord_lang_id, localized_name.clone());
-                option = option.name_localized(discord_lang_id, localized_name);
-            }
-
-            let localized_desc = language.language_command_desc();
-            if localized_desc != fallback_language.language_command_desc() {
-                command = command.description_localized(discord_lang_id, localized_desc);
-            }
-
-            let localized_arg_dec = language.language_command_arg_desc();
-            if localized_arg_dec != fallback_language.language_command_arg_desc() {
-                option = option.description_localized(discord_lang_id, localized_arg_dec);
-            }
-        }
-
-        option = option.add_string_choice(language.name(), language.id());
-    }
-
-    command.add_option(option)
+/// Where the map-by-map results of a set are loaded from
+#[derive(Clone)]
+pub enum MatchResultsSource {
+    Sendou(SendouClient),
+    /// A directory of saved `matches/<id>.data` responses, named `match-<id>.json` or as recorded
+    /// by a [`SnapshotRecorder`]
+    Saved(PathBuf),
 }
 
-#[allow(clippy::too_many_arguments)]
-async fn create_discord_channels(
-    discord_http: &DiscordHttp,
-    guild_id: GuildId,
-    mut guild_channels_by_name: HashMap<String, ChannelId>,
-    category: ChannelId,
-    language_command_id: CommandId,
-    get_tournament: &impl GetTournamentFn,
-    players: &mut SwitzerlandPlayerMap,
-) -> Result<DiscordChannelsMap> {
-    println!("Creating Discord channels...");
-
-    let mut channels = HashMap::new();
-
-    let me_user = discord_http.cache().current_user();
-    let commentators_role = env("DISCORD_COMMENTATORS_ROLE_ID")?;
-
-    for team in get_tournament().await?.context.teams {
-        if team.check_ins.is_empty() {
-            continue;
-        }
-        let player = team.members.first().unwrap();
-
-        let switzerland_player = players.get_mut(&PlayerId::Sendou(player.user_id)).unwrap();
-        let guess_language = switzerland_player.language.is_none();
-        let language = switzerland_player.language.get_or_insert_with(|| {
-            player
-                .country
-                .as_ref()
-                .and_then(|lang| Language::guess_from_country(lang))
-                .unwrap_or_default()
-        });
-        let language_command =
-            CommandIdDisplay(language.language_command_name(), language_command_id);
-
-        let user = player.discord_id.to_user(discord_http).await?;
-        let channel_name = format!("switzerland-{}", user.name.replace('.', ""));
-        let channel = if let Some(channel) = guild_channels_by_name.remove(&channel_name) {
-            channel.say(discord_http, language.bot_crashed()).await?;
-            channel
-        } else {
-            let channel = guild_id
-                .create_channel(
-                    discord_http,
-                    CreateChannel::new(channel_name)
-                        .category(category)
-                        .permissions([
-                            PermissionOverwrite {
-                                allow: Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES,
-                                deny: Permissions::empty(),
-                                kind: PermissionOverwriteType::Member(me_user.id),
-                            },
-                            PermissionOverwrite {
-                                allow: USER_CHANNEL_PERMS,
-                                deny: Permissions::empty(),
-                                kind: PermissionOverwriteType::Member(user.id),
-                            },
-                            PermissionOverwrite {
-                                allow: Permissions::VIEW_CHANNEL,
-                                deny: Permissions::SEND_MESSAGES,
-                                kind: PermissionOverwriteType::Role(commentators_role),
-                            },
-                            PermissionOverwrite {
-                                allow: Permissions::empty(),
-                                deny: Permissions::VIEW_CHANNEL,
-                                kind: PermissionOverwriteType::Role(guild_id.everyone_role()),
-                            },
-                        ]),
-                )
-                .await?;
-            channel
-                .say(discord_http, language.channel_explanation(user.mention()))
-                .await?;
-            channel.id
-        };
-        if guess_language {
-            channel
-                .say(
-                    discord_http,
-                    language.language_command_explanation(&language_command, *language),
+impl MatchResultsSource {
+    async fn get(&self, tournament_id: SendouId, match_id: SendouId) -> Result<Vec<MatchResult>> {
+        match self {
+            Self::Sendou(sendou) => sendou.match_results(tournament_id, match_id).await,
+            Self::Saved(dir) => {
+                let mut path = dir.join(format!("match-{match_id}.json"));
+                if !path.exists()
+                    && let Some(recorded) = latest_snapshot(dir, &format!("match-{match_id}"))?
+                {
+                    path = recorded;
+                }
+                Ok(
+                    serde_json::from_reader::<_, TurboStreamed<ToMatchResponse>>(fs::File::open(
+                        path,
+                    )?)?
+                    .0
+                    .to_match
+                    .data
+                    .results,
                 )
-                .await?;
+            }
         }
-        channels.insert(team.id, channel);
     }
-
-    Ok(channels)
 }
 
+/// Processes matches as they complete until the tournament is finalized. If `live` is false, the
+/// tournament is only processed once and no commands are read from the console.
 #[allow(clippy::too_many_arguments)]
 async fn run_tournament(
-    http_client: &ReqwestClient,
-    http: &DiscordHttp,
+    match_results: &MatchResultsSource,
+    output: &impl TournamentOutput,
     players: &mut SwitzerlandPlayerMap,
     teams: &TeamsMap<'_>,
-    discord_user_languages: &DashMap<UserId, Language>,
-    discord_channels: &DiscordChannelsMap,
-    get_tournament: &impl GetTournamentFn,
+    record: &mut TournamentRecord,
+    config: &SeasonConfig,
+    run_state: &Arc<RunStateFile>,
+    poll_tournament: &impl PollTournamentFn,
+    live: bool,
 ) -> Result<()> {
-    let mut command_engine = CommandEngine::new()?;
+    let mut command_engine = live
+        .then(|| CommandEngine::new(run_state.clone()))
+        .transpose()?;
+    let mut printer: Box<dyn IoWrite> = match &command_engine {
+        Some(command_engine) => Box::new(command_engine.printer.clone()),
+        None => Box::new(io::stdout()),
+    };
 
-    let mut completed_matches = HashSet::new();
+    let mut completed_matches = run_state.read(|state| state.processed_matches.clone());
+    let mut schedule = PollSchedule::default();
 
-    let animation_generator = AsyncAnimationGenerator::new().await?;
     let top_player_count = leaderboard_count(players.len());
     let show_placement_count = show_placement_count(players.len());
 
-    let new_players = loop {
-        let tournament = get_tournament().await?;
+    let (new_players, new_sets) = loop {
+        let poll = poll_tournament().await?;
+        let (delay, message) = schedule.next_delay_logged(&poll);
+        if live && let Some(message) = message {
+            writeln!(printer, "{message}")?;
+        }
+        if let Some(command_engine) = &mut command_engine
+            && !mem::take(&mut command_engine.reprocess)
+            && !poll.changed
+        {
+            command_engine.pump(delay).await?;
+            continue;
+        }
+        let tournament = poll.tournament;
         let rounds: HashMap<_, _> = tournament
             .data
             .rounds
@@ -539,6 +428,10 @@ async fn run_tournament(
             .collect();
 
         let mut new_players = players.clone();
+        let mut period = config
+            .tournament_rating_period
+            .then(|| rating::TournamentRatingPeriod::new(players));
+        let mut new_sets = vec![];
         let mut ranked_players = RankVec::new(
             players
                 .values()
@@ -547,8 +440,11 @@ async fn run_tournament(
                 .collect_vec(),
         );
 
-        for tourney_match in tournament.data.matches {
-            if command_engine.ignored_matches.contains(&tourney_match.id) {
+        for &tourney_match in &tournament.data.matches {
+            if command_engine
+                .as_ref()
+                .is_some_and(|engine| engine.ignored_matches.contains(&tourney_match.id))
+            {
                 continue;
             }
 
@@ -576,15 +472,14 @@ async fn run_tournament(
                 }
             }
 
-            let get_player = |opponent: &Option<TournamentMatchOpponent>| {
-                teams
-                    .get(&opponent.unwrap().id.expect("Null opponent in ready match"))
-                    .and_then(|team| {
-                        let player_id = PlayerId::Sendou(team.members.first().unwrap().user_id);
-                        let player = new_players.get(&player_id)?;
-                        Some((team, player_id, player.rating, player.language.unwrap()))
-                    })
-                    .unwrap()
+            let get_team = |opponent: &Option<TournamentMatchOpponent>| {
+                let team = teams[&opponent.unwrap().id.expect("Null opponent in ready match")];
+                let player_ids = team.player_ids();
+                let ratings = player_ids
+                    .iter()
+                    .map(|id| new_players[id].rating)
+                    .collect_vec();
+                (team, player_ids, ratings)
             };
             if tourney_match.status == TournamentMatchStatus::Ready
                 && (tourney_match.opponent1.is_none() || tourney_match.opponent2.is_none())
@@ -592,38 +487,75 @@ async fn run_tournament(
                 continue; // BYE
             }
             if tourney_match.status != TournamentMatchStatus::Completed {
-                completed_matches.remove(&tourney_match.id);
+                if completed_matches.remove(&tourney_match.id) {
+                    // Announce the set again once it's been completed with its new results
+                    let prefix = progress_message_prefix(tourney_match.id);
+                    run_state.update(|state| {
+                        state.processed_matches.remove(&tourney_match.id);
+                        state
+                            .progress_messages
+                            .retain(|key, _| !key.starts_with(&prefix));
+                    })?;
+                }
                 continue;
             }
             let new_match = completed_matches.insert(tourney_match.id);
-            let (team1, player1, rating1, language1) = get_player(&tourney_match.opponent1);
-            let (team2, player2, rating2, language2) = get_player(&tourney_match.opponent2);
-            let (new_rating1, new_rating2) = glicko2(
-                &rating1,
-                &rating2,
-                &match tourney_match.opponent1.unwrap().result.unwrap() {
-                    TournamentMatchResult::Win => Outcomes::WIN,
-                    TournamentMatchResult::Loss => Outcomes::LOSS,
-                },
-                &Glicko2Config::default(),
+            if new_match {
+                run_state.update(|state| state.processed_matches.insert(tourney_match.id))?;
+            }
+            let (team1, players1, ratings1) = get_team(&tourney_match.opponent1);
+            let (team2, players2, ratings2) = get_team(&tourney_match.opponent2);
+            let set_record = SetRecord {
+                match_id: tourney_match.id,
+                team1: players1.clone(),
+                team2: players2.clone(),
+                result: tourney_match.opponent1.unwrap().result.unwrap().into(),
+                score: (
+                    tourney_match.opponent1.unwrap().score,
+                    tourney_match.opponent2.unwrap().score,
+                ),
+            };
+            let (new_ratings1, new_ratings2) = rating::rate_tournament_set(
+                config,
+                period.as_mut(),
+                &set_record,
+                &ratings1,
+                &ratings2,
             );
+            new_sets.push(set_record);
             if new_match {
-                writeln!(command_engine.printer, "In match {}:", tourney_match.id)?;
+                match tournament
+                    .data
+                    .round_location(tourney_match.round_id)
+                    .zip(tournament.data.round_progress(tourney_match.round_id))
+                {
+                    Some(((stage, _), (round, total))) => writeln!(
+                        printer,
+                        "In match {} ({} round {round} of {total}):",
+                        tourney_match.id, stage.name
+                    )?,
+                    None => writeln!(printer, "In match {}:", tourney_match.id)?,
+                }
             }
             let mut update_player = async |opponent: Option<TournamentMatchOpponent>,
                                            team: &TournamentTeam,
                                            other_team: &TournamentTeam,
                                            player,
-                                           new_rating,
-                                           language|
+                                           new_rating|
                    -> Result<()> {
                 let player = new_players.get_mut(player).unwrap();
                 let old_player = player.clone();
-                player.rating = new_rating;
-                player.unrated = false;
-                if player.rating.deviation <= MAXIMUM_CALCED_RD {
-                    player.calced = true;
-                }
+                let language = old_player.language.unwrap_or_default();
+                rating::apply_rating(config, player, new_rating);
+                player.history.push(RatingHistoryEntry {
+                    tournament_id: tournament.context.id,
+                    match_id: tourney_match.id,
+                    date: tournament.context.start_time,
+                    old_rating: old_player.rating,
+                    new_rating,
+                    opponents: other_team.player_ids(),
+                    result: opponent.unwrap().result.unwrap().into(),
+                });
 
                 let old_rank = ranked_players
                     .get_rank_and_remove(&old_player.id, old_player.rating)
@@ -635,62 +567,51 @@ async fn run_tournament(
                     || new_rank <= show_placement_count)
                     .then_some((old_rank, new_rank));
 
-                if !new_match {
-                    return Ok(());
+                if new_match {
+                    writeln!(
+                        printer,
+                        "  {}",
+                        format_player_simply(Some(&old_player), player, false, true)
+                    )?;
                 }
-
-                writeln!(
-                    command_engine.printer,
-                    "  {}",
-                    format_player_simply(Some(&old_player), player, false, true)
-                )?;
-                send_progress_message_to_player(
-                    http_client,
-                    http,
-                    discord_channels,
-                    discord_user_languages,
-                    &tournament.context,
-                    &tourney_match,
-                    &animation_generator,
+                output.send_progress(ProgressUpdate {
+                    match_results,
+                    config,
+                    tournament_context: &tournament.context,
+                    tourney_match: &tourney_match,
                     team,
                     other_team,
-                    opponent.unwrap(),
-                    &old_player,
-                    player,
+                    my_result: opponent.unwrap(),
+                    old_player: &old_player,
+                    new_player: player,
                     rank_change,
-                    top_player_count,
-                    language,
-                )?;
+                    top_rank: top_player_count,
+                    original_language: language,
+                })?;
                 Ok(())
             };
-            update_player(
-                tourney_match.opponent1,
-                team1,
-                team2,
-                &player1,
-                new_rating1,
-                language1,
-            )
-            .await?;
-            update_player(
-                tourney_match.opponent2,
-                team2,
-                team1,
-                &player2,
-                new_rating2,
-                language2,
-            )
-            .await?;
+            for (player, new_rating) in players1.iter().zip(new_ratings1) {
+                update_player(tourney_match.opponent1, team1, team2, player, new_rating).await?;
+            }
+            for (player, new_rating) in players2.iter().zip(new_ratings2) {
+                update_player(tourney_match.opponent2, team2, team1, player, new_rating).await?;
+            }
         }
 
+        output.players_updated(&new_players)?;
+
         if tournament.context.is_finalized {
-            break new_players;
+            break (new_players, new_sets);
         }
 
-        command_engine.pump().await?;
+        match &mut command_engine {
+            Some(command_engine) => command_engine.pump(delay).await?,
+            None => break (new_players, new_sets),
+        }
     };
 
     *players = new_players;
+    record.sets = new_sets;
     Ok(())
 }
 
@@ -705,23 +626,25 @@ struct CommandEngine {
     action_recv: UnboundedReceiver<CommandEngineAction>,
     printer: SharedWriter,
     ignored_matches: HashSet<SendouId>,
-    interval: Interval,
+    /// Where skipped matches are saved, so that they stay skipped if the run is restarted
+    run_state: Arc<RunStateFile>,
+    /// Whether the tournament needs to be processed again even if it hasn't changed
+    reprocess: bool,
 }
 
 impl CommandEngine {
-    fn new() -> Result<Self> {
+    fn new(run_state: Arc<RunStateFile>) -> Result<Self> {
         let (action_send, action_recv) = tokio::sync::mpsc::unbounded_channel();
         let (rl, printer) = Readline::new("command> ".to_string())?;
         Self::start_task(rl, action_send, printer.clone());
 
-        let mut interval = time::interval(POLL_TIME);
-        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
-
         Ok(Self {
             action_recv,
             printer,
-            ignored_matches: HashSet::new(),
-            interval,
+            ignored_matches: run_state
+                .read(|state| state.skipped_matches.iter().copied().collect()),
+            run_state,
+            reprocess: true,
         })
     }
 
@@ -782,21 +705,27 @@ impl CommandEngine {
         });
     }
 
-    async fn pump(&mut self) -> Result<()> {
+    async fn pump(&mut self, delay: Duration) -> Result<()> {
+        let timeout = sleep(delay);
+        tokio::pin!(timeout);
         loop {
             let action = tokio::select! {
-                _ = self.interval.tick() => CommandEngineAction::Poll(false),
+                _ = &mut timeout => CommandEngineAction::Poll(false),
                 action = self.action_recv.recv() => action.expect("Action input thread exited unexpectedly without Error"),
             };
             match action {
                 CommandEngineAction::Poll(forced) => {
                     if forced {
+                        self.reprocess = true;
                         writeln!(self.printer, "Polling now")?;
                     }
                     break;
                 }
                 CommandEngineAction::SkipMatch(id) => {
                     self.ignored_matches.insert(id);
+                    self.run_state
+                        .update(|state| state.skipped_matches.insert(id))?;
+                    self.reprocess = true;
                     writeln!(self.printer, "Ignoring match {id}")?;
                 }
                 CommandEngineAction::Error(err) => return Err(err.into()),
@@ -810,152 +739,6 @@ impl CommandEngine {
     }
 }
 
-#[allow(clippy::too_many_arguments)]
-fn send_progress_message_to_player(
-    http_client: &ReqwestClient,
-    discord_http: &DiscordHttp,
-    discord_channels: &DiscordChannelsMap,
-    discord_user_languages: &DashMap<UserId, Language>,
-    tournament_context: &TournamentContext,
-    tourney_match: &TournamentMatch,
-    animation_generator: &AsyncAnimationGenerator,
-    team: &TournamentTeam,
-    other_team: &TournamentTeam,
-    my_result: TournamentMatchOpponent,
-    old_player: &SwitzerlandPlayer,
-    new_player: &SwitzerlandPlayer,
-    rank_change: Option<(usize, usize)>,
-    top_rank: usize,
-    original_language: Language,
-) -> Result<()> {
-    let Some(discord_channel) = discord_channels.get(&team.id).copied() else {
-        return Ok(());
-    };
-
-    fn calc_percentage(deviation: f64) -> f64 {
-        const DEFAULT_RD: f64 = 350.0;
-        1.0 - (deviation - MAXIMUM_CALCED_RD) / (DEFAULT_RD - MAXIMUM_CALCED_RD)
-    }
-    let old_calc_percent = if old_player.unrated {
-        0.0
-    } else {
-        calc_percentage(old_player.rating.deviation)
-    };
-    let new_calc_percent = calc_percentage(new_player.rating.deviation);
-
-    let mut power_status = if old_player.calced {
-        PowerStatus::SetPlayed {
-            matches: Default::default(),
-            old_power: old_player.rating.rating,
-            new_power: new_player.rating.rating,
-            rank_change: rank_change.map(|(old, new)| (old as u32, new as u32)),
-            top_rank: top_rank as u32,
-        }
-    } else if new_player.calced {
-        PowerStatus::Calculated {
-            prev_calc_percent: old_calc_percent,
-            power: new_player.rating.rating,
-            rank: rank_change.map(|(_, new)| new as u32),
-            top_rank: top_rank as u32,
-        }
-    } else {
-        PowerStatus::Calculating {
-            old_calc_percent,
-            new_calc_percent,
-        }
-    };
-
-    let player_discord_id = team.members.first().unwrap().discord_id;
-    let language = discord_user_languages
-        .get(&player_discord_id)
-        .as_deref()
-        .copied()
-        .unwrap_or(original_language);
-
-    let message = format_link(
-        &language.round_played(
-            match my_result.result.unwrap() {
-                TournamentMatchResult::Win => language.to_animation_language().win(),
-                TournamentMatchResult::Loss => language.to_animation_language().lose(),
-            },
-            &other_team.members.first().unwrap().username,
-        ),
-        &format!(
-            "<https://sendou.ink/to/{}/matches/{}>",
-            tournament_context.id, tourney_match.id,
-        ),
-    );
-
-    let http_client = http_client.clone();
-    let discord_http = discord_http.clone();
-    let tourney_id = tournament_context.id;
-    let set_id = tourney_match.id;
-    let animation_generator = animation_generator.clone();
-    let my_team_id = team.id;
-    tokio::spawn(
-        async move {
-            if let PowerStatus::SetPlayed { matches, .. } = &mut power_status {
-                let match_results = http_client
-                    .get(format!(
-                        "https://sendou.ink/to/{tourney_id}/matches/{set_id}.data?_routes=features/tournament-match/routes/to.$id.matches.$mid"
-                    ))
-                    .send()
-                    .await?
-                    .error_for_status()?
-                    .json::<TurboStreamed<ToMatchResponse>>()
-                    .await?
-                    .0
-                    .to_match
-                    .data
-                    .results;
-                for (i, result) in match_results.into_iter().enumerate() {
-                    matches[i] = if result.winner_team_id == my_team_id {
-                        MatchOutcome::Win
-                    } else {
-                        MatchOutcome::Lose
-                    };
-                }
-            }
-            let filename = format!("set-{set_id}-{my_team_id}.webp");
-            let animation = animation_generator
-                .generate(power_status, language.into())
-                .await?;
-            // discord_channel
-            //     .create_permission(
-            //         discord_http.http(),
-            //         PermissionOverwrite {
-            //             allow: USER_CHANNEL_PERMS,
-            //             deny: Permissions::empty(),
-            //             kind: PermissionOverwriteType::Member(player_discord_id),
-            //         },
-            //     )
-            //     .await?;
-            let send_result = discord_channel
-                .send_message(
-                    discord_http,
-                    CreateMessage::new()
-                        .content(message)
-                        .add_file(CreateAttachment::bytes(animation.clone(), &filename)),
-                )
-                .await;
-            if let Err(result) = send_result {
-                if let Ok(backups_dir) = env::<PathBuf>("GENERATED_ANIM_BACKUPS_DIR")
-                    && let Err(err) = fs::write(backups_dir.join(&filename), &animation) {
-                        println!("Failed to save backup animation file for {set_id}: {err}");
-                    }
-                return Err(result.into());
-            }
-            Ok::<(), Error>(())
-        }
-        .then(async move |result| {
-            if let Err(err) = result {
-                println!("Failed to send results message for set {set_id}: {err}");
-            }
-        }),
-    );
-    Ok(())
-}
-
 fn format_link(body: &str, link: &str) -> String {
     if !body.chars().any(is_emoji_presentation) {
         format!("[{body}]({link})")
@@ -966,10 +749,13 @@ fn format_link(body: &str, link: &str) -> String {
 
 fn finalize_tournament(
     out_db: &Path,
+    old_db: &Database,
     old_players: &SwitzerlandPlayerMap,
     new_players: SwitzerlandPlayerMap,
+    record: TournamentRecord,
 ) -> Result<Database> {
-    let new_db = Database::new_from_map(new_players);
+    let mut new_db = old_db.with_players(new_players);
+    new_db.add_tournament(record);
     new_db.write(out_db)?;
 
     println!("\nSP comparison (switzerland-power-calc compare):");
@@ -978,161 +764,107 @@ fn finalize_tournament(
     Ok(new_db)
 }
 
-#[allow(clippy::too_many_arguments)]
-async fn send_summaries_to_discord(
-    discord_http: &DiscordHttp,
-    guild: &Guild,
-    moderator_channel: ChannelId,
-    leaderboard_channel: ChannelId,
-    old_players: &SwitzerlandPlayerMap,
-    teams: &TeamsMap<'_>,
-    new_db: &Database,
-    get_tournament: &impl GetTournamentFn,
-) -> Result<()> {
-    println!("\nSending comparison to Discord...");
-    let tournament = get_tournament().await?;
-    let player_id_to_discord_id = teams
-        .values()
-        .filter(|team| !team.check_ins.is_empty())
-        .map(|team| team.members.first().unwrap())
-        .map(|player| (PlayerId::Sendou(player.user_id), player.discord_id))
-        .collect::<HashMap<_, _>>();
-
-    let mut players_in_discord = HashSet::new();
-    for user_id in player_id_to_discord_id.values().copied() {
-        if guild.member(discord_http, user_id).await.is_ok() {
-            players_in_discord.insert(user_id);
+/// Writes the podium and standings of every stage of the tournament. `mention` gives the mention
+/// to show next to a player on the podium, if any.
+fn format_results(
+    message: &mut String,
+    tournament: &Tournament,
+    mention: impl Fn(&TournamentTeamMember) -> Option<String>,
+) {
+    let find_team = |team_id| {
+        tournament
+            .context
+            .teams
+            .iter()
+            .find(|x| x.id == team_id)
+            .unwrap()
+    };
+    let mut print_results = |title, results: &StageResults| {
+        let _ = writeln!(message, "## {title}");
+        for (team_id, emoji) in results.podium.iter().flatten().zip(['🥇', '🥈', '🥉']) {
+            let team = find_team(*team_id);
+            let mentions = team.members.iter().filter_map(&mention).join(", ");
+            let _ = writeln!(
+                message,
+                "- {emoji} {}{}",
+                team.display_name(),
+                if !mentions.is_empty() {
+                    format!(" ({mentions})")
+                } else {
+                    "".to_string()
+                },
+            );
         }
-    }
-
-    {
-        let mut message = String::new();
-        let _ = writeln!(
-            message,
-            "And that concludes {}! Thank you all for participating, and I hope you had a good time.",
-            tournament.context.name
-        );
-        let mut print_results = |title, results: &[SendouId; 3]| {
-            let _ = writeln!(message, "## {title}");
-            for (team_id, emoji) in results.iter().zip(['🥇', '🥈', '🥉']) {
-                let player = tournament
-                    .context
-                    .teams
-                    .iter()
-                    .find(|x| x.id == *team_id)
-                    .unwrap()
-                    .members
-                    .first()
-                    .unwrap();
+        for (group_number, standings) in &results.standings {
+            if results.standings.len() == 1 {
+                let _ = writeln!(message, "### Standings");
+            } else {
+                let _ = writeln!(message, "### Group {group_number} standings");
+            }
+            for (index, standing) in standings.iter().enumerate() {
                 let _ = writeln!(
                     message,
-                    "- {emoji} {}{}",
-                    player.username,
-                    if players_in_discord.contains(&player.discord_id) {
-                        format!(" ({})", player.discord_id.mention())
-                    } else {
-                        "".to_string()
-                    },
+                    "{}. {}: {}–{} (maps {}–{}, opponent win rate {:.0}%)",
+                    index + 1,
+                    find_team(standing.team_id).display_name(),
+                    standing.wins,
+                    standing.losses,
+                    standing.map_wins,
+                    standing.map_losses,
+                    standing.opponent_win_rate * 100.0,
                 );
             }
-        };
-
-        match &compute_results(&tournament.data)[..] {
-            [] => {}
-            [(_, results)] => print_results("Results".to_string(), results),
-            all_results => {
-                for (bracket, results) in all_results {
-                    print_results(format!("{bracket} results"), results);
-                }
-            }
         }
+    };
 
-        let _ = writeln!(message, "## Switzerland Power changes");
-        let show_placement_count = show_placement_count(new_db.players.len());
-        let should_show_rank = |player: &SwitzerlandPlayer| {
-            player
-                .rank
-                .is_some_and(|r| r.get() as usize <= show_placement_count)
-        };
-        for new_player in &new_db.players {
-            if new_player.rating.deviation > MAXIMUM_CALCED_RD {
-                continue;
-            }
-            let Some(discord_id) = player_id_to_discord_id.get(&new_player.id) else {
-                continue;
-            };
-            if !players_in_discord.contains(discord_id) {
-                continue;
-            }
-            let old_result = old_players.get(&new_player.id);
-            if let Some(old_result) = old_result
-                && old_result.rating == new_player.rating
-            {
-                continue;
+    match &compute_results(&tournament.data)[..] {
+        [] => {}
+        [results] => print_results("Results".to_string(), results),
+        all_results => {
+            for results in all_results {
+                print_results(format!("{} results", results.name), results);
             }
-            let _ = writeln!(
-                message,
-                "- {} {}",
-                discord_id.mention(),
-                format_player_rank_summary(
-                    old_result,
-                    new_player,
-                    old_result.is_some_and(should_show_rank) || should_show_rank(new_player),
-                    false,
-                )
-            );
         }
-
-        moderator_channel
-            .send_message(discord_http, CreateMessage::new().content(message))
-            .await?;
     }
+}
 
-    {
-        let old_leaderboard_messages = leaderboard_channel
-            .messages_iter(discord_http.http())
-            .try_collect::<Vec<_>>()
-            .await?;
-        for message in
-            generate_leaderboard_messages(old_players, new_db, &player_id_to_discord_id, 2000)
-        {
-            leaderboard_channel
-                .send_message(
-                    discord_http,
-                    CreateMessage::new()
-                        .content(message)
-                        .flags(MessageFlags::SUPPRESS_NOTIFICATIONS),
-                )
-                .await?;
+/// Splits a message into messages of at most `max_message_len` bytes, breaking between lines.
+/// Lines that are too long for a message on their own are broken up wherever they need to be.
+fn split_message(message: &str, max_message_len: usize) -> Vec<String> {
+    let mut messages = vec![];
+    let mut current = String::new();
+    for mut line in message.lines() {
+        if !current.is_empty() && current.len() + 1 + line.len() > max_message_len {
+            messages.push(mem::take(&mut current));
         }
-        let allow_bulk_delete_timestamp =
-            Timestamp::from_unix_timestamp(Timestamp::now().unix_timestamp() - 60 * 60 * 24 * 13)
-                .unwrap();
-        for messages in old_leaderboard_messages
-            .into_iter()
-            .chunks(100)
-            .into_iter()
-            .map(Itertools::collect_vec)
-        {
-            if messages
-                .iter()
-                .all(|x| x.timestamp > allow_bulk_delete_timestamp)
-            {
-                leaderboard_channel
-                    .delete_messages(discord_http.http(), messages)
-                    .await?;
-            } else {
-                for message in messages {
-                    message.delete(discord_http).await?;
-                }
-            }
+        if !current.is_empty() {
+            current.push('\n');
         }
+        while current.len() + line.len() > max_message_len {
+            let split = line
+                .floor_char_boundary(max_message_len - current.len())
+                .max(line.ceil_char_boundary(1));
+            current.push_str(&line[..split]);
+            messages.push(mem::take(&mut current));
+            line = &line[split..];
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        messages.push(current);
     }
+    messages
+}
 
-    Ok(())
+/// The final placements of a stage. Standings are only computed for round robin and Swiss stages,
+/// with one table per group. Stages split into multiple groups have no overall podium.
+struct StageResults<'a> {
+    name: &'a str,
+    podium: Option<[SendouId; 3]>,
+    standings: Vec<(u32, Vec<Standing>)>,
 }
 
-fn compute_results(tournament_data: &TournamentData) -> Vec<(&str, [SendouId; 3])> {
+fn compute_results(tournament_data: &TournamentData) -> Vec<StageResults<'_>> {
     let find_match = |round_id| {
         tournament_data
             .matches
@@ -1226,24 +958,124 @@ fn compute_results(tournament_data: &TournamentData) -> Vec<(&str, [SendouId; 3]
         .stages
         .iter()
         .sorted_by_key(|x| &x.name)
-        .filter_map(|stage| match stage.settings {
-            TournamentStageSettings::SingleElimination {} => {
-                Some((stage.name.as_str(), compute_results_for_se(stage.id)?))
-            }
-            TournamentStageSettings::DoubleElimination {} => {
-                Some((stage.name.as_str(), compute_results_for_de(stage.id)?))
-            }
-            _ => None,
+        .filter_map(|stage| {
+            let (podium, standings) = match stage.settings {
+                TournamentStageSettings::SingleElimination {} => {
+                    (Some(compute_results_for_se(stage.id)?), vec![])
+                }
+                TournamentStageSettings::DoubleElimination {} => {
+                    (Some(compute_results_for_de(stage.id)?), vec![])
+                }
+                TournamentStageSettings::RoundRobin { .. }
+                | TournamentStageSettings::Swiss { .. } => {
+                    let standings = tournament_data
+                        .groups
+                        .iter()
+                        .filter(|x| x.stage_id == stage.id)
+                        .sorted_by_key(|x| x.number)
+                        .map(|group| (group.number, compute_standings(tournament_data, group.id)))
+                        .filter(|(_, standings)| !standings.is_empty())
+                        .collect_vec();
+                    let podium = match standings.as_slice() {
+                        [] => return None,
+                        [(_, standings)] if stage.settings.group_count().unwrap_or(1) <= 1 => {
+                            Some(standings.iter().map(|x| x.team_id).collect_array()?)
+                        }
+                        _ => None,
+                    };
+                    (podium, standings)
+                }
+            };
+            Some(StageResults {
+                name: &stage.name,
+                podium,
+                standings,
+            })
         })
         .collect()
 }
 
-async fn clean_up_discord_channels(
-    http: &DiscordHttp,
-    channels: impl IntoIterator<Item = ChannelId>,
-) {
-    println!("Deleting Discord channels...");
-    for channel in channels {
-        let _ = channel.delete(http.http()).await;
+#[cfg(test)]
+mod test {
+    use crate::config::SeasonConfig;
+    use crate::db::{PlayerId, SwitzerlandPlayerMap};
+    use crate::sendou::mock_server::{MockResponse, MockSendouServer};
+    use crate::sendou::output::ConsoleOutput;
+    use crate::sendou::polling::TournamentPoller;
+    use crate::sendou::run_state::RunStateFile;
+    use crate::sendou::{MatchResultsSource, initialize_teams, run_tournament, split_message};
+    use std::sync::Arc;
+
+    #[test]
+    fn split_message_test() {
+        assert_eq!(split_message("aaaa\nbbb\ncc\nd", 8), ["aaaa\nbbb", "cc\nd"]);
+        assert_eq!(
+            split_message("aa\nbbbbbbbbbbbbbbbbbbbb\nc", 8),
+            ["aa", "bbbbbbbb", "bbbbbbbb", "bbbb\nc"]
+        );
+        assert_eq!(split_message("ééééé", 5), ["éé", "éé", "é"]);
+        assert!(split_message("", 8).is_empty());
+        let long = "x".repeat(4500);
+        assert!(
+            split_message(&format!("header\n{long}\nfooter"), 2000)
+                .iter()
+                .all(|message| message.len() <= 2000)
+        );
+    }
+
+    #[tokio::test]
+    async fn mock_tournament_test() {
+        let server = MockSendouServer::with_fixtures();
+        server.queue(
+            "/to/1234/register.data?_routes=features/tournament/routes/to.$id",
+            MockResponse::status(502),
+        );
+        let client = server.client();
+        let poller = TournamentPoller::new(&client, 1234);
+        let poll_tournament = async || poller.poll().await;
+
+        let config = SeasonConfig::default();
+        let tournament = poll_tournament().await.unwrap().tournament;
+        let mut players = SwitzerlandPlayerMap::new();
+        let (teams, mut record) =
+            initialize_teams(&config, &tournament, &mut players, Some(&client))
+                .await
+                .unwrap();
+        let run_state = Arc::new(RunStateFile::in_memory(1234));
+        run_tournament(
+            &MatchResultsSource::Sendou(client.clone()),
+            &ConsoleOutput::default(),
+            &mut players,
+            &teams,
+            &mut record,
+            &config,
+            &run_state,
+            &poll_tournament,
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(record.participants.len(), 4);
+        assert_eq!(
+            record.sets.iter().map(|x| x.match_id).collect::<Vec<_>>(),
+            [11, 12, 21, 22]
+        );
+        run_state.read(|state| {
+            assert_eq!(
+                state.processed_matches.iter().copied().collect::<Vec<_>>(),
+                [11, 12, 21, 22]
+            )
+        });
+        let rating = |id| players[&PlayerId::Sendou(id)].rating.rating;
+        assert!(rating(101) > rating(103));
+        assert!(rating(102) > rating(104));
+        // The stages have already been created, so no seeds should have been sent
+        assert!(
+            server
+                .requested_paths()
+                .iter()
+                .all(|path| path.starts_with("/to/1234/register.data"))
+        );
     }
 }
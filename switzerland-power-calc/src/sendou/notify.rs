@@ -0,0 +1,470 @@
+use crate::db::{PlayerId, SwitzerlandPlayerMap};
+use crate::sendou::lang::Language;
+use crate::sendou::output::ProgressUpdate;
+use crate::sendou::overlay::OverlayServer;
+use crate::sendou::run_state::{RunStateFile, progress_message_key};
+use crate::sendou::schema::{SendouId, TournamentMatchResult};
+use crate::{Error, Result};
+use itertools::Itertools;
+use reqwest::multipart::{Form, Part};
+use serde::Serialize;
+use serenity::FutureExt;
+use serenity::all::{MessageId, UserId};
+use std::fs;
+use std::mem;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use switzerland_power_animated::{AsyncAnimationGenerator, MatchOutcome, PowerStatus};
+use tokio::task::JoinSet;
+
+/// A player's progress after a set, along with their animation
+#[derive(Clone, Serialize)]
+pub struct ProgressNotification {
+    pub tournament_id: SendouId,
+    pub set_id: SendouId,
+    pub team_id: SendouId,
+    pub player_id: SendouId,
+    pub player_name: String,
+    /// Only used by Discord, and left out of what the other sinks publish
+    #[serde(skip)]
+    pub discord_id: UserId,
+    /// The number of players on the team, who all see notifications sent to the team
+    pub team_size: usize,
+    pub language: Language,
+    /// The localized description of the set, such as who it was won against
+    pub text: String,
+    pub link: String,
+    pub old_power: f64,
+    pub new_power: f64,
+    pub rank_change: Option<(usize, usize)>,
+    /// The file name of the WebP animation
+    pub animation_file: String,
+    #[serde(skip)]
+    pub animation: Arc<[u8]>,
+}
+
+#[cfg(test)]
+impl ProgressNotification {
+    /// A notification of Alpha (player 101 on team 1) winning a set in tournament 1234
+    pub fn test(set_id: SendouId, new_power: f64) -> Self {
+        Self {
+            tournament_id: 1234,
+            set_id,
+            team_id: 1,
+            player_id: 101,
+            player_name: "Alpha".to_string(),
+            discord_id: UserId::new(900),
+            team_size: 1,
+            language: Language::EnglishUnitedStates,
+            text: "Won against Bravo".to_string(),
+            link: format!("https://sendou.ink/to/1234/matches/{set_id}"),
+            old_power: 2000.0,
+            new_power,
+            rank_change: Some((3, 2)),
+            animation_file: format!("set-{set_id}-1-101.webp"),
+            animation: Arc::from(*b"RIFF....WEBP"),
+        }
+    }
+}
+
+/// Somewhere players' progress is sent to after each set
+#[serenity::async_trait]
+pub trait NotificationSink: Send + Sync {
+    /// The name that deliveries to this sink are recorded under in the run state
+    fn name(&self) -> &'static str;
+
+    /// Sends a notification, returning the ID of the Discord message if one was sent
+    async fn notify(&self, notification: &ProgressNotification) -> Result<Option<MessageId>>;
+
+    /// Called with everyone's current ratings each time the tournament has been processed
+    fn players_updated(&self, _players: &SwitzerlandPlayerMap) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Where progress is sent besides Discord
+#[derive(Default)]
+pub struct SinkOptions<'a> {
+    /// See [`DirectorySink`]
+    pub dir: Option<&'a Path>,
+    /// See [`WebhookSink`]
+    pub webhook_url: Option<&'a str>,
+    /// See [`OverlayServer`]
+    pub overlay_addr: Option<SocketAddr>,
+}
+
+impl SinkOptions<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.dir.is_none() && self.webhook_url.is_none() && self.overlay_addr.is_none()
+    }
+
+    /// Creates a notifier that sends to each of these sinks
+    pub async fn create_notifier(&self, run_state: Arc<RunStateFile>) -> Result<ProgressNotifier> {
+        let mut notifier = ProgressNotifier::new(run_state).await?;
+        if let Some(dir) = self.dir {
+            notifier.add_sink(DirectorySink::new(dir)?);
+        }
+        if let Some(webhook_url) = self.webhook_url {
+            notifier.add_sink(WebhookSink::new(webhook_url));
+        }
+        if let Some(overlay_addr) = self.overlay_addr {
+            notifier.add_sink(OverlayServer::start(overlay_addr).await?);
+        }
+        Ok(notifier)
+    }
+}
+
+/// Generates players' animations and sends them to every [`NotificationSink`] in the background
+pub struct ProgressNotifier {
+    sinks: Vec<Arc<dyn NotificationSink>>,
+    run_state: Arc<RunStateFile>,
+    animation_generator: AsyncAnimationGenerator,
+    deliveries: Mutex<JoinSet<()>>,
+}
+
+impl ProgressNotifier {
+    pub async fn new(run_state: Arc<RunStateFile>) -> Result<Self> {
+        Ok(Self {
+            sinks: vec![],
+            run_state,
+            animation_generator: AsyncAnimationGenerator::new().await?,
+            deliveries: Mutex::new(JoinSet::new()),
+        })
+    }
+
+    pub fn add_sink(&mut self, sink: impl NotificationSink + 'static) {
+        self.sinks.push(Arc::new(sink));
+    }
+
+    pub fn players_updated(&self, players: &SwitzerlandPlayerMap) -> Result<()> {
+        for sink in &self.sinks {
+            sink.players_updated(players)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for every progress message that's been sent to finish being delivered
+    pub async fn wait_for_deliveries(&self) {
+        let deliveries = mem::take(&mut *self.deliveries.lock().unwrap());
+        deliveries.join_all().await;
+    }
+
+    /// Sends a player their progress, unless it's already been sent
+    pub fn send(&self, update: ProgressUpdate) -> Result<()> {
+        let ProgressUpdate {
+            match_results,
+            config,
+            tournament_context,
+            tourney_match,
+            team,
+            other_team,
+            my_result,
+            old_player,
+            new_player,
+            rank_change,
+            top_rank,
+            original_language,
+        } = update;
+        let player_id = new_player.id.unwrap_sendou();
+        let message_key = progress_message_key(tourney_match.id, team.id, player_id);
+        let sink_names = self.sinks.iter().map(|sink| sink.name()).collect_vec();
+        let sink_names = self.run_state.sinks_to_notify(&message_key, &sink_names);
+        if sink_names.is_empty() {
+            return Ok(());
+        }
+
+        let old_calc_percent = if old_player.unrated {
+            0.0
+        } else {
+            config.calc_percentage(old_player.rating.deviation)
+        };
+        let new_calc_percent = config.calc_percentage(new_player.rating.deviation);
+
+        let mut power_status = if old_player.calced {
+            PowerStatus::SetPlayed {
+                matches: Default::default(),
+                old_power: old_player.rating.rating,
+                new_power: new_player.rating.rating,
+                rank_change: rank_change.map(|(old, new)| (old as u32, new as u32)),
+                top_rank: top_rank as u32,
+            }
+        } else if new_player.calced {
+            PowerStatus::Calculated {
+                prev_calc_percent: old_calc_percent,
+                power: new_player.rating.rating,
+                rank: rank_change.map(|(_, new)| new as u32),
+                top_rank: top_rank as u32,
+            }
+        } else {
+            PowerStatus::Calculating {
+                old_calc_percent,
+                new_calc_percent,
+            }
+        };
+
+        let Some(player_discord_id) = team
+            .members
+            .iter()
+            .find(|member| PlayerId::Sendou(member.user_id) == new_player.id)
+            .map(|member| member.discord_id)
+        else {
+            return Ok(());
+        };
+        let language = self
+            .run_state
+            .read(|state| state.languages.get(&player_discord_id).copied())
+            .unwrap_or(original_language);
+
+        let set_id = tourney_match.id;
+        let mut notification = ProgressNotification {
+            tournament_id: tournament_context.id,
+            set_id,
+            team_id: team.id,
+            player_id,
+            player_name: new_player.display_name().into_owned(),
+            discord_id: player_discord_id,
+            team_size: team.members.len(),
+            language,
+            text: language
+                .round_played(
+                    match my_result.result.unwrap() {
+                        TournamentMatchResult::Win => language.to_animation_language().win(),
+                        TournamentMatchResult::Loss => language.to_animation_language().lose(),
+                    },
+                    other_team.display_name(),
+                )
+                .into_owned(),
+            link: format!(
+                "https://sendou.ink/to/{}/matches/{set_id}",
+                tournament_context.id
+            ),
+            old_power: old_player.rating.rating,
+            new_power: new_player.rating.rating,
+            rank_change,
+            animation_file: format!("{message_key}.webp"),
+            animation: Arc::new([]),
+        };
+
+        let match_results = match_results.clone();
+        let animation_generator = self.animation_generator.clone();
+        let sinks = self
+            .sinks
+            .iter()
+            .filter(|sink| sink_names.contains(&sink.name()))
+            .cloned()
+            .collect_vec();
+        self.run_state.queue_message(&message_key, &sink_names)?;
+        let run_state = self.run_state.clone();
+        let mut deliveries = self.deliveries.lock().unwrap();
+        while deliveries.try_join_next().is_some() {}
+        deliveries.spawn(
+            async move {
+                if let PowerStatus::SetPlayed { matches, .. } = &mut power_status {
+                    let match_results = match_results
+                        .get(notification.tournament_id, set_id)
+                        .await?;
+                    for (i, result) in match_results.into_iter().enumerate() {
+                        matches[i] = if result.winner_team_id == notification.team_id {
+                            MatchOutcome::Win
+                        } else {
+                            MatchOutcome::Lose
+                        };
+                    }
+                }
+                notification.animation = animation_generator
+                    .generate(power_status, language.into())
+                    .await?
+                    .into();
+
+                for sink in sinks {
+                    match sink.notify(&notification).await {
+                        Ok(discord_message) => {
+                            run_state.message_delivered(
+                                &message_key,
+                                sink.name(),
+                                discord_message,
+                            )?;
+                        }
+                        Err(err) => println!(
+                            "Failed to send results message for set {set_id} to {}: {err}",
+                            sink.name()
+                        ),
+                    }
+                }
+                Ok::<(), Error>(())
+            }
+            .then(async move |result| {
+                if let Err(err) = result {
+                    println!("Failed to send results message for set {set_id}: {err}");
+                }
+            }),
+        );
+        Ok(())
+    }
+}
+
+/// Writes each animation to a directory, along with a `manifest.json` listing every notification
+pub struct DirectorySink {
+    dir: PathBuf,
+    manifest_lock: Mutex<()>,
+}
+
+impl DirectorySink {
+    pub fn new(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            manifest_lock: Mutex::new(()),
+        })
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        self.dir.join("manifest.json")
+    }
+}
+
+#[serenity::async_trait]
+impl NotificationSink for DirectorySink {
+    fn name(&self) -> &'static str {
+        "directory"
+    }
+
+    async fn notify(&self, notification: &ProgressNotification) -> Result<Option<MessageId>> {
+        fs::write(
+            self.dir.join(&notification.animation_file),
+            &notification.animation,
+        )?;
+
+        let _lock = self.manifest_lock.lock().unwrap();
+        let manifest_path = self.manifest_path();
+        let mut manifest = if manifest_path.exists() {
+            serde_json::from_slice::<Vec<serde_json::Value>>(&fs::read(&manifest_path)?)?
+        } else {
+            vec![]
+        };
+        // A set that's been reopened and completed again replaces its old notification
+        manifest.retain(|entry| entry["animation_file"] != notification.animation_file.as_str());
+        manifest.push(serde_json::to_value(notification)?);
+        let temp_path = manifest_path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(&manifest)?)?;
+        fs::rename(temp_path, manifest_path)?;
+        Ok(None)
+    }
+}
+
+/// POSTs each notification to a URL as `multipart/form-data`, with the notification as JSON in
+/// `payload_json` and the animation in `file`
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+        }
+    }
+}
+
+#[serenity::async_trait]
+impl NotificationSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn notify(&self, notification: &ProgressNotification) -> Result<Option<MessageId>> {
+        let form = Form::new()
+            .part(
+                "payload_json",
+                Part::text(serde_json::to_string(notification)?).mime_str("application/json")?,
+            )
+            .part(
+                "file",
+                Part::bytes(notification.animation.to_vec())
+                    .file_name(notification.animation_file.clone())
+                    .mime_str("image/webp")?,
+            );
+        self.client
+            .post(&self.url)
+            .multipart(form)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sendou::mock_server::{MockResponse, MockSendouServer, temp_path};
+    use crate::sendou::notify::{
+        DirectorySink, NotificationSink, ProgressNotification, WebhookSink,
+    };
+    use std::fs;
+
+    #[tokio::test]
+    async fn directory_sink_test() {
+        let dir = temp_path("notify");
+        let sink = DirectorySink::new(&dir).unwrap();
+        sink.notify(&ProgressNotification::test(11, 2010.0))
+            .await
+            .unwrap();
+        sink.notify(&ProgressNotification::test(12, 2020.0))
+            .await
+            .unwrap();
+        sink.notify(&ProgressNotification::test(11, 1990.0))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fs::read(dir.join("set-11-1-101.webp")).unwrap(),
+            b"RIFF....WEBP"
+        );
+        let manifest = serde_json::from_slice::<Vec<serde_json::Value>>(
+            &fs::read(dir.join("manifest.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(manifest[0]["set_id"], 12);
+        assert_eq!(manifest[1]["set_id"], 11);
+        assert_eq!(manifest[1]["new_power"], 1990.0);
+        assert!(manifest[1].get("discord_id").is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn webhook_sink_test() {
+        let server = MockSendouServer::start();
+        server.route("/hook", MockResponse::status(204));
+        let sink = WebhookSink::new(&format!("{}/hook", server.base_url()));
+        sink.notify(&ProgressNotification::test(11, 2010.0))
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert!(
+            requests[0]
+                .header("content-type")
+                .unwrap()
+                .starts_with("multipart/form-data")
+        );
+        let body = String::from_utf8_lossy(&requests[0].body);
+        assert!(body.contains(r#""set_id":11"#));
+        assert!(!body.contains("discord_id"));
+        assert!(body.contains(r#"filename="set-11-1-101.webp""#));
+        assert!(body.contains("RIFF....WEBP"));
+
+        let failing_sink = WebhookSink::new(&format!("{}/missing", server.base_url()));
+        assert!(
+            failing_sink
+                .notify(&ProgressNotification::test(11, 2010.0))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use crate::Result;
+use crate::config::SeasonConfig;
+use crate::db::{Database, SwitzerlandPlayer, SwitzerlandPlayerMap};
+use crate::sendou::MatchResultsSource;
+use crate::sendou::format_results;
+use crate::sendou::lang::Language;
+use crate::sendou::notify::ProgressNotifier;
+use crate::sendou::schema::{
+    TournamentContext, TournamentMatch, TournamentMatchOpponent, TournamentTeam,
+};
+use crate::sendou::types::{GetTournamentFn, TeamsMap};
+use serenity::all::UserId;
+use std::collections::HashMap;
+
+/// A player's Switzerland Power after a set they played
+pub struct ProgressUpdate<'a> {
+    pub match_results: &'a MatchResultsSource,
+    pub config: &'a SeasonConfig,
+    pub tournament_context: &'a TournamentContext,
+    pub tourney_match: &'a TournamentMatch,
+    pub team: &'a TournamentTeam,
+    pub other_team: &'a TournamentTeam,
+    pub my_result: TournamentMatchOpponent,
+    pub old_player: &'a SwitzerlandPlayer,
+    pub new_player: &'a SwitzerlandPlayer,
+    pub rank_change: Option<(usize, usize)>,
+    pub top_rank: usize,
+    pub original_language: Language,
+}
+
+/// Where a live tournament is announced to players and moderators. The ratings themselves are
+/// always calculated and logged to the console, regardless of the output.
+pub trait TournamentOutput {
+    /// Sets up wherever players are sent their progress, once the tournament has started
+    async fn create_channels(
+        &mut self,
+        get_tournament: &impl GetTournamentFn,
+        players: &mut SwitzerlandPlayerMap,
+    ) -> Result<()>;
+
+    /// Sends a player their progress after a set. Called every time the tournament is processed,
+    /// so it's up to the output to only send each update once.
+    fn send_progress(&self, update: ProgressUpdate) -> Result<()>;
+
+    /// Called with everyone's current ratings each time the tournament has been processed
+    fn players_updated(&self, players: &SwitzerlandPlayerMap) -> Result<()>;
+
+    /// Announces the results of the tournament and everyone's new Switzerland Power
+    async fn send_summary(
+        &self,
+        old_players: &SwitzerlandPlayerMap,
+        teams: &TeamsMap<'_>,
+        new_db: &Database,
+        get_tournament: &impl GetTournamentFn,
+    ) -> Result<()>;
+
+    /// Replaces the previous leaderboard with the one in `new_db`
+    async fn update_leaderboard(
+        &self,
+        old_players: &SwitzerlandPlayerMap,
+        teams: &TeamsMap<'_>,
+        new_db: &Database,
+    ) -> Result<()>;
+
+    /// The languages players chose for their messages during the tournament
+    fn user_languages(&self) -> HashMap<UserId, Language>;
+
+    /// Cleans up anything that was only needed while the tournament was running
+    async fn finish(self) -> Result<()>;
+}
+
+/// Only logs to the console, for running tournaments without Discord. Progress can still be sent
+/// to other sinks with a [`ProgressNotifier`].
+#[derive(Default)]
+pub struct ConsoleOutput {
+    notifier: Option<ProgressNotifier>,
+}
+
+impl ConsoleOutput {
+    pub fn new(notifier: Option<ProgressNotifier>) -> Self {
+        Self { notifier }
+    }
+}
+
+impl TournamentOutput for ConsoleOutput {
+    async fn create_channels(
+        &mut self,
+        _get_tournament: &impl GetTournamentFn,
+        _players: &mut SwitzerlandPlayerMap,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn send_progress(&self, update: ProgressUpdate) -> Result<()> {
+        match &self.notifier {
+            Some(notifier) => notifier.send(update),
+            None => Ok(()),
+        }
+    }
+
+    fn players_updated(&self, players: &SwitzerlandPlayerMap) -> Result<()> {
+        match &self.notifier {
+            Some(notifier) => notifier.players_updated(players),
+            None => Ok(()),
+        }
+    }
+
+    async fn send_summary(
+        &self,
+        _old_players: &SwitzerlandPlayerMap,
+        _teams: &TeamsMap<'_>,
+        _new_db: &Database,
+        get_tournament: &impl GetTournamentFn,
+    ) -> Result<()> {
+        let tournament = get_tournament().await?;
+        let mut message = String::new();
+        format_results(&mut message, &tournament, |_| None);
+        if !message.is_empty() {
+            println!("\n{message}");
+        }
+        Ok(())
+    }
+
+    async fn update_leaderboard(
+        &self,
+        _old_players: &SwitzerlandPlayerMap,
+        _teams: &TeamsMap<'_>,
+        _new_db: &Database,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn user_languages(&self) -> HashMap<UserId, Language> {
+        HashMap::new()
+    }
+
+    async fn finish(self) -> Result<()> {
+        if let Some(notifier) = &self.notifier {
+            notifier.wait_for_deliveries().await;
+        }
+        Ok(())
+    }
+}
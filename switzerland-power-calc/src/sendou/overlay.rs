@@ -0,0 +1,357 @@
+use crate::Result;
+use crate::counts::leaderboard_count;
+use crate::db::{PlayerId, SwitzerlandPlayerMap};
+use crate::sendou::notify::{NotificationSink, ProgressNotification};
+use itertools::Itertools;
+use serde::Serialize;
+use serenity::all::MessageId;
+use serenity::futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+
+/// A player on the live leaderboard
+#[derive(Clone, Serialize)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub player_id: PlayerId,
+    pub name: String,
+    pub power: f64,
+}
+
+/// The longest request or header line that's accepted
+const MAX_LINE_LENGTH: u64 = 8 * 1024;
+/// The most headers that are accepted in a request
+const MAX_HEADER_COUNT: usize = 64;
+
+/// What's sent to clients of `/events` as it happens
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OverlayEvent<'a> {
+    Set(&'a ProgressNotification),
+    Leaderboard { players: &'a [LeaderboardEntry] },
+}
+
+#[derive(Default)]
+struct OverlayState {
+    sets: Mutex<Vec<ProgressNotification>>,
+    animations: Mutex<HashMap<String, Arc<[u8]>>>,
+    leaderboard: Mutex<Vec<LeaderboardEntry>>,
+}
+
+/// An HTTP server for stream overlays, such as an OBS browser source. It serves:
+/// - `/sets`: every notification sent so far, as JSON
+/// - `/animations/<file>`: the animation of a notification
+/// - `/leaderboard`: the current leaderboard, as JSON
+/// - `/events`: a WebSocket sending each new notification and leaderboard as they happen
+#[derive(Clone)]
+pub struct OverlayServer {
+    #[cfg(test)]
+    addr: SocketAddr,
+    state: Arc<OverlayState>,
+    events: broadcast::Sender<String>,
+}
+
+impl OverlayServer {
+    pub async fn start(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(OverlayState::default());
+        let (events, _) = broadcast::channel(64);
+        let server_state = state.clone();
+        let server_events = events.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let state = server_state.clone();
+                let events = server_events.subscribe();
+                tokio::spawn(async move {
+                    if let Err(err) = handle_connection(stream, &state, events).await {
+                        println!("Overlay connection failed: {err}");
+                    }
+                });
+            }
+        });
+        println!("Serving overlay on http://{addr}");
+        Ok(Self {
+            #[cfg(test)]
+            addr,
+            state,
+            events,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Forgets the notifications of the previous tournament, keeping the leaderboard
+    pub fn clear(&self) {
+        self.state.sets.lock().unwrap().clear();
+        self.state.animations.lock().unwrap().clear();
+    }
+
+    fn publish(&self, event: OverlayEvent) -> Result<()> {
+        // Sending only fails when no one's listening
+        let _ = self.events.send(serde_json::to_string(&event)?);
+        Ok(())
+    }
+}
+
+#[serenity::async_trait]
+impl NotificationSink for OverlayServer {
+    fn name(&self) -> &'static str {
+        "overlay"
+    }
+
+    async fn notify(&self, notification: &ProgressNotification) -> Result<Option<MessageId>> {
+        self.state.animations.lock().unwrap().insert(
+            notification.animation_file.clone(),
+            notification.animation.clone(),
+        );
+        {
+            let mut sets = self.state.sets.lock().unwrap();
+            sets.retain(|old_set| old_set.animation_file != notification.animation_file);
+            sets.push(notification.clone());
+        }
+        self.publish(OverlayEvent::Set(notification))?;
+        Ok(None)
+    }
+
+    fn players_updated(&self, players: &SwitzerlandPlayerMap) -> Result<()> {
+        let leaderboard = players
+            .values()
+            .filter(|player| player.show_rank())
+            .sorted_by(|a, b| a.descending_rating_order_cmp(b))
+            .take(leaderboard_count(players.len()))
+            .enumerate()
+            .map(|(index, player)| LeaderboardEntry {
+                rank: index + 1,
+                player_id: player.id.clone(),
+                name: player.display_name().into_owned(),
+                power: player.rating.rating,
+            })
+            .collect_vec();
+        self.publish(OverlayEvent::Leaderboard {
+            players: &leaderboard,
+        })?;
+        *self.state.leaderboard.lock().unwrap() = leaderboard;
+        Ok(())
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    state: &OverlayState,
+    events: broadcast::Receiver<String>,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    read_line_limited(&mut reader, &mut line).await?;
+    let path = line.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+    let mut websocket_key = None;
+    for header_index in 0.. {
+        if header_index == MAX_HEADER_COUNT {
+            return Err("Overlay request has too many headers".into());
+        }
+        if read_line_limited(&mut reader, &mut line).await? == 0 {
+            return Ok(());
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':')
+            && key.trim().eq_ignore_ascii_case("sec-websocket-key")
+        {
+            websocket_key = Some(value.trim().to_string());
+        }
+    }
+    let mut stream = reader.into_inner();
+
+    if path == "/events"
+        && let Some(websocket_key) = websocket_key
+    {
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                    derive_accept_key(websocket_key.as_bytes())
+                )
+                .as_bytes(),
+            )
+            .await?;
+        let websocket = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+        return forward_events(websocket, events).await;
+    }
+
+    let (status, content_type, body) = match path.as_str() {
+        "/sets" => (
+            "200 OK",
+            "application/json",
+            serde_json::to_vec(&*state.sets.lock().unwrap())?,
+        ),
+        "/leaderboard" => (
+            "200 OK",
+            "application/json",
+            serde_json::to_vec(&*state.leaderboard.lock().unwrap())?,
+        ),
+        _ => match path
+            .strip_prefix("/animations/")
+            .and_then(|file| state.animations.lock().unwrap().get(file).cloned())
+        {
+            Some(animation) => ("200 OK", "image/webp", animation.to_vec()),
+            None => ("404 Not Found", "text/plain", b"Not Found".to_vec()),
+        },
+    };
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+                body.len()
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.write_all(&body).await?;
+    Ok(())
+}
+
+/// Reads a line into `line`, failing instead of reading any more than [`MAX_LINE_LENGTH`]
+async fn read_line_limited(reader: &mut BufReader<TcpStream>, line: &mut String) -> Result<usize> {
+    line.clear();
+    let read = reader.take(MAX_LINE_LENGTH).read_line(line).await?;
+    if read as u64 == MAX_LINE_LENGTH && !line.ends_with('\n') {
+        return Err("Overlay request line is too long".into());
+    }
+    Ok(read)
+}
+
+async fn forward_events(
+    mut websocket: WebSocketStream<TcpStream>,
+    mut events: broadcast::Receiver<String>,
+) -> Result<()> {
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if websocket.send(Message::Text(event)).await.is_err() {
+                        // The client has gone away
+                        return Ok(());
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            message = websocket.next() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return Ok(()),
+                Some(Ok(_)) => continue,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::{PlayerId, SwitzerlandPlayer, SwitzerlandPlayerMap};
+    use crate::sendou::notify::{NotificationSink, ProgressNotification};
+    use crate::sendou::overlay::OverlayServer;
+    use serenity::futures::StreamExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[tokio::test]
+    async fn overlay_test() {
+        let server = OverlayServer::start("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let base_url = format!("http://{}", server.local_addr());
+        let (mut events, _) =
+            tokio_tungstenite::connect_async(format!("ws://{}/events", server.local_addr()))
+                .await
+                .unwrap();
+
+        server
+            .notify(&ProgressNotification::test(11, 2010.0))
+            .await
+            .unwrap();
+        let Some(Ok(Message::Text(event))) = events.next().await else {
+            panic!("Expected a set event");
+        };
+        let event = serde_json::from_str::<serde_json::Value>(&event).unwrap();
+        assert_eq!(event["type"], "set");
+        assert_eq!(event["set_id"], 11);
+        assert!(event.get("discord_id").is_none());
+
+        let sets = reqwest::get(format!("{base_url}/sets"))
+            .await
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+        assert_eq!(sets[0]["player_name"], "Alpha");
+        assert!(sets[0].get("discord_id").is_none());
+        let animation = reqwest::get(format!("{base_url}/animations/set-11-1-101.webp"))
+            .await
+            .unwrap();
+        assert_eq!(animation.headers()["content-type"], "image/webp");
+        assert_eq!(&animation.bytes().await.unwrap()[..], b"RIFF....WEBP");
+        let missing = reqwest::get(format!("{base_url}/animations/missing.webp"))
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), 404);
+
+        let mut long_request = TcpStream::connect(server.local_addr()).await.unwrap();
+        long_request
+            .write_all(format!("GET /{} HTTP/1.1\r\n", "a".repeat(10_000)).as_bytes())
+            .await
+            .unwrap();
+        // The connection is closed without a response, which can show up as a reset
+        let mut response = vec![];
+        let read = long_request.read_to_end(&mut response).await;
+        assert!(read.is_err() || response.is_empty());
+
+        let mut players = SwitzerlandPlayerMap::new();
+        for (id, rating, calced) in [
+            (101, 2010.0, true),
+            (102, 2100.0, true),
+            (103, 2200.0, false),
+        ] {
+            let mut player = SwitzerlandPlayer {
+                id: PlayerId::Sendou(id),
+                calced,
+                ..Default::default()
+            };
+            player.rating.rating = rating;
+            players.insert(player.id.clone(), player);
+        }
+        server.players_updated(&players).unwrap();
+        let Some(Ok(Message::Text(event))) = events.next().await else {
+            panic!("Expected a leaderboard event");
+        };
+        let event = serde_json::from_str::<serde_json::Value>(&event).unwrap();
+        assert_eq!(event["type"], "leaderboard");
+        let leaderboard = reqwest::get(format!("{base_url}/leaderboard"))
+            .await
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+        assert_eq!(leaderboard, event["players"]);
+        assert_eq!(leaderboard.as_array().unwrap().len(), 2);
+        assert_eq!(leaderboard[0]["player_id"], 102);
+        assert_eq!(leaderboard[1]["rank"], 2);
+    }
+}
@@ -0,0 +1,434 @@
+use crate::config::SeasonConfig;
+use crate::db::{Database, SetResult, SwitzerlandPlayer};
+use crate::sendou::api::SendouClient;
+use crate::sendou::discord::{localized_command, localized_option};
+use crate::sendou::format_link;
+use crate::sendou::lang::Language;
+use crate::sendou::schema::SendouId;
+use crate::{Result, format_sp};
+use itertools::Itertools;
+use serenity::all::{
+    CacheHttp, CommandId, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse, GuildId,
+    UserId,
+};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+const DEFAULT_HISTORY_COUNT: usize = 5;
+const MAX_HISTORY_COUNT: usize = 20;
+/// How closely a player's name has to match a query for them to be found by it
+const MIN_NAME_SIMILARITY: f64 = 0.8;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum PlayerCommand {
+    Sp,
+    Rank,
+    History,
+}
+
+impl PlayerCommand {
+    fn create(self) -> CreateCommand {
+        let player_option = localized_option(
+            CommandOptionType::String,
+            Language::player_arg_name,
+            Language::player_arg_desc,
+        );
+        match self {
+            Self::Sp => localized_command(Language::sp_command_name, Language::sp_command_desc)
+                .add_option(player_option),
+            Self::Rank => {
+                localized_command(Language::rank_command_name, Language::rank_command_desc)
+                    .add_option(player_option)
+            }
+            Self::History => localized_command(
+                Language::history_command_name,
+                Language::history_command_desc,
+            )
+            .add_option(player_option)
+            .add_option(
+                localized_option(
+                    CommandOptionType::Integer,
+                    Language::count_arg_name,
+                    Language::count_arg_desc,
+                )
+                .min_int_value(1)
+                .max_int_value(MAX_HISTORY_COUNT as u64),
+            ),
+        }
+    }
+}
+
+/// Slash commands that let players look up their own or anyone else's Switzerland Power
+pub struct PlayerCommands {
+    db: Mutex<Database>,
+    sendou: SendouClient,
+    command_ids: RwLock<HashMap<CommandId, PlayerCommand>>,
+    /// The sendou.ink users that Discord users have linked their accounts to
+    linked_users: Mutex<HashMap<UserId, SendouId>>,
+    /// Where to save languages that players choose to the database
+    db_path: Option<PathBuf>,
+}
+
+impl PlayerCommands {
+    pub fn new(db: Database, sendou: SendouClient) -> Self {
+        Self {
+            db: Mutex::new(db),
+            sendou,
+            command_ids: RwLock::new(HashMap::new()),
+            linked_users: Mutex::new(HashMap::new()),
+            db_path: None,
+        }
+    }
+
+    pub fn with_db_path(mut self, db_path: &Path) -> Self {
+        self.db_path = Some(db_path.to_path_buf());
+        self
+    }
+
+    pub fn database(&self) -> Database {
+        self.db.lock().unwrap().clone()
+    }
+
+    /// Answers using the ratings in `db` from now on
+    pub fn set_database(&self, db: Database) {
+        *self.db.lock().unwrap() = db;
+    }
+
+    pub async fn create_commands(&self, http: impl CacheHttp, guild_id: GuildId) -> Result<()> {
+        for command in [
+            PlayerCommand::Sp,
+            PlayerCommand::Rank,
+            PlayerCommand::History,
+        ] {
+            let command_id = guild_id.create_command(&http, command.create()).await?.id;
+            self.command_ids
+                .write()
+                .unwrap()
+                .insert(command_id, command);
+        }
+        Ok(())
+    }
+
+    pub async fn delete_commands(&self, http: impl CacheHttp, guild_id: GuildId) -> Result<()> {
+        let command_ids = self
+            .command_ids
+            .write()
+            .unwrap()
+            .drain()
+            .map(|(command_id, _)| command_id)
+            .collect_vec();
+        for command_id in command_ids {
+            guild_id.delete_command(http.http(), command_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Responds to `command` if it's one of these commands, returning whether it was. The
+    /// response is in `language` if the user has chosen one, otherwise the language of their
+    /// Discord client.
+    pub async fn handle(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        language: Option<Language>,
+    ) -> bool {
+        let Some(player_command) = self
+            .command_ids
+            .read()
+            .unwrap()
+            .get(&command.data.id)
+            .copied()
+        else {
+            return false;
+        };
+        let language = language
+            .or_else(|| Language::from_discord_id(&command.locale))
+            .unwrap_or_default();
+        let default_language = Language::default();
+        let query = command
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == default_language.player_arg_name())
+            .and_then(|option| option.value.as_str());
+        let count = command
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == default_language.count_arg_name())
+            .and_then(|option| option.value.as_i64())
+            .map_or(DEFAULT_HISTORY_COUNT, |count| count as usize);
+
+        // Looking up a linked account can take longer than Discord waits for a response
+        let defer = CreateInteractionResponse::Defer(
+            CreateInteractionResponseMessage::new().ephemeral(true),
+        );
+        if let Err(e) = command.create_response(ctx, defer).await {
+            println!("Failed to respond to {} command: {e}", command.data.name);
+            return true;
+        }
+
+        let linked_user = match query {
+            Some(_) => None,
+            None => self.linked_user(command.user.id).await,
+        };
+        let response = {
+            let mut db = self.db.lock().unwrap();
+            let player = match query {
+                Some(query) => find_player(&mut db, query),
+                None => linked_user.and_then(|user_id| {
+                    db.players
+                        .iter()
+                        .position(|player| player.sendou_id() == Some(user_id))
+                }),
+            };
+            match player {
+                Some(player) => answer(player_command, &db, &db.players[player], count, language),
+                None => language.player_not_found().into_owned(),
+            }
+        };
+
+        let response = EditInteractionResponse::new().content(response);
+        if let Err(e) = command.edit_response(ctx, response).await {
+            println!("Failed to respond to {} command: {e}", command.data.name);
+        }
+        true
+    }
+
+    /// Saves `language` to the database for the player that a Discord user has linked their
+    /// account to, returning whether they could be found
+    pub async fn save_language(&self, discord_id: UserId, language: Language) -> Result<bool> {
+        let Some(db_path) = &self.db_path else {
+            return Ok(false);
+        };
+        let Some(user_id) = self.linked_user(discord_id).await else {
+            return Ok(false);
+        };
+        let mut db = self.db.lock().unwrap();
+        let Some(player) = db
+            .players
+            .iter_mut()
+            .find(|player| player.sendou_id() == Some(user_id))
+        else {
+            return Ok(false);
+        };
+        player.language = Some(language);
+        db.write(db_path)?;
+        Ok(true)
+    }
+
+    /// The sendou.ink user that a Discord user has linked their account to, if any
+    async fn linked_user(&self, discord_id: UserId) -> Option<SendouId> {
+        if let Some(&user_id) = self.linked_users.lock().unwrap().get(&discord_id) {
+            return Some(user_id);
+        }
+        // sendou.ink looks users up by their Discord ID as well as their custom URL
+        let user_id = self
+            .sendou
+            .user(&discord_id.to_string())
+            .await
+            .ok()?
+            .user
+            .id;
+        self.linked_users
+            .lock()
+            .unwrap()
+            .insert(discord_id, user_id);
+        Some(user_id)
+    }
+}
+
+/// The index of the player whose name best matches `query`, or whose sendou.ink ID is `query`.
+/// Players whose names aren't similar enough to `query` aren't found.
+fn find_player(db: &mut Database, query: &str) -> Option<usize> {
+    let mut result = None;
+    db.for_each_matching_mut(&vec![query.to_string()], true, |_, index| {
+        result = Some(index)
+    });
+    let player = &db.players[result?];
+    let matches_id = query
+        .parse::<SendouId>()
+        .is_ok_and(|id| player.sendou_id() == Some(id));
+    let similarity =
+        strsim::jaro_winkler(&query.to_lowercase(), &player.display_name().to_lowercase());
+    (matches_id || similarity >= MIN_NAME_SIMILARITY).then_some(result?)
+}
+
+fn answer(
+    command: PlayerCommand,
+    db: &Database,
+    player: &SwitzerlandPlayer,
+    history_count: usize,
+    language: Language,
+) -> String {
+    match command {
+        PlayerCommand::Sp => describe_sp(&db.config, player, language),
+        PlayerCommand::Rank => describe_rank(db, player, language),
+        PlayerCommand::History => describe_history(player, history_count, language),
+    }
+}
+
+fn describe_sp(config: &SeasonConfig, player: &SwitzerlandPlayer, language: Language) -> String {
+    if player.calced {
+        language
+            .player_sp(&player.display_name(), &format_sp(player.rating, false))
+            .into_owned()
+    } else {
+        let percent = config
+            .calc_percentage(player.rating.deviation)
+            .clamp(0.0, 1.0)
+            * 100.0;
+        language
+            .player_calculating(&player.display_name(), percent)
+            .into_owned()
+    }
+}
+
+fn describe_rank(db: &Database, player: &SwitzerlandPlayer, language: Language) -> String {
+    match player.rank {
+        Some(rank) => {
+            let ranked_count = db.players.iter().filter(|p| p.show_rank()).count();
+            language
+                .player_rank(&player.display_name(), rank.get(), ranked_count)
+                .into_owned()
+        }
+        None => language
+            .player_unranked(&player.display_name())
+            .into_owned(),
+    }
+}
+
+/// Describes the last `count` tournaments of a player, most recent first
+fn describe_history(player: &SwitzerlandPlayer, count: usize, language: Language) -> String {
+    let tournaments = player
+        .history
+        .iter()
+        .chunk_by(|entry| entry.tournament_id)
+        .into_iter()
+        .map(|(tournament_id, entries)| (tournament_id, entries.collect_vec()))
+        .collect_vec();
+    if tournaments.is_empty() {
+        return language.history_empty(&player.display_name()).into_owned();
+    }
+
+    let mut message = language.history_header(&player.display_name()).into_owned();
+    for (tournament_id, entries) in tournaments.iter().rev().take(count) {
+        let first = entries.first().unwrap();
+        let last = entries.last().unwrap();
+        let wins = entries
+            .iter()
+            .filter(|entry| entry.result == SetResult::Win)
+            .count();
+        let _ = write!(
+            message,
+            "\n- {}: {} → {} ({:+.1}), {wins}–{}",
+            format_link(
+                &first.date.format("%Y-%m-%d").to_string(),
+                &format!("<https://sendou.ink/to/{tournament_id}>")
+            ),
+            format_sp(first.old_rating, false),
+            format_sp(last.new_rating, false),
+            last.new_rating.rating - first.old_rating.rating,
+            entries.len() - wins,
+        );
+    }
+    message
+}
+
+#[cfg(test)]
+mod test {
+    use crate::db::{Database, PlayerId, RatingHistoryEntry, SetResult, SwitzerlandPlayer};
+    use crate::sendou::lang::Language;
+    use crate::sendou::player_commands::{
+        describe_history, describe_rank, describe_sp, find_player,
+    };
+    use chrono::{TimeZone, Utc};
+
+    fn player(id: u32, name: &str, rating: f64, deviation: f64) -> SwitzerlandPlayer {
+        let mut player = SwitzerlandPlayer {
+            id: PlayerId::Sendou(id),
+            display_name: Some(name.to_string()),
+            calced: deviation <= 170.0,
+            ..Default::default()
+        };
+        player.rating.rating = rating;
+        player.rating.deviation = deviation;
+        player
+    }
+
+    #[test]
+    fn player_commands_test() {
+        let mut db = Database::new();
+        let mut alpha = player(101, "Alpha", 1600.0, 100.0);
+        for (tournament_id, match_id, old_rating, new_rating, result) in [
+            (1, 11, 1500.0, 1550.0, SetResult::Win),
+            (1, 12, 1550.0, 1530.0, SetResult::Loss),
+            (2, 21, 1530.0, 1600.0, SetResult::Win),
+        ] {
+            let mut entry = RatingHistoryEntry {
+                tournament_id,
+                match_id,
+                date: Utc
+                    .with_ymd_and_hms(2025, 1, tournament_id, 0, 0, 0)
+                    .unwrap(),
+                old_rating: alpha.rating,
+                new_rating: alpha.rating,
+                opponents: vec![PlayerId::Sendou(102)],
+                result,
+            };
+            entry.old_rating.rating = old_rating;
+            entry.new_rating.rating = new_rating;
+            alpha.history.push(entry);
+        }
+        db.players = vec![
+            alpha,
+            player(102, "Bravo", 1700.0, 100.0),
+            player(103, "Charlie", 1800.0, 260.0),
+        ];
+        db.sort();
+        let language = Language::EnglishUnitedStates;
+
+        let alpha = find_player(&mut db, "alpah").unwrap();
+        assert_eq!(db.players[alpha].display_name(), "Alpha");
+        assert_eq!(find_player(&mut db, "102"), Some(1));
+        assert_eq!(find_player(&mut db, "qwzxv"), None);
+        assert_eq!(find_player(&mut db, "999"), None);
+        let charlie = find_player(&mut db, "charlie").unwrap();
+
+        assert_eq!(
+            describe_sp(&db.config, &db.players[alpha], language),
+            "Alpha has 1600.0 SP"
+        );
+        assert_eq!(
+            describe_sp(&db.config, &db.players[charlie], language),
+            "Charlie's Switzerland Power is still being calculated (50% complete)"
+        );
+        assert_eq!(
+            describe_rank(&db, &db.players[alpha], language),
+            "Alpha is ranked #2 of 2"
+        );
+        assert_eq!(
+            describe_rank(&db, &db.players[charlie], language),
+            "Charlie isn't ranked yet"
+        );
+        assert_eq!(
+            describe_history(&db.players[alpha], 5, language),
+            "Alpha's last tournaments:\n\
+            - [2025-01-02](<https://sendou.ink/to/2>): 1530.0 SP → 1600.0 SP (+70.0), 1–0\n\
+            - [2025-01-01](<https://sendou.ink/to/1>): 1500.0 SP → 1530.0 SP (+30.0), 1–1"
+        );
+        assert_eq!(
+            describe_history(&db.players[alpha], 1, language)
+                .lines()
+                .count(),
+            2
+        );
+        assert_eq!(
+            describe_history(&db.players[charlie], 5, language),
+            "Charlie hasn't played in any tournaments yet"
+        );
+    }
+}
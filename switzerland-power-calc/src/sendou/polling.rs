@@ -0,0 +1,280 @@
+use crate::Result;
+use crate::error::{Error, ErrorKind};
+use crate::sendou::api::{CacheValidators, SendouClient};
+use crate::sendou::schema::{SendouId, Tournament, TournamentMatchStatus};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How often to poll while sets are being played
+pub const ACTIVE_POLL_TIME: Duration = Duration::from_secs(5);
+/// How often to poll when nothing is being played, before backing off
+pub const IDLE_POLL_TIME: Duration = Duration::from_secs(10);
+/// The longest to ever wait between polls
+pub const MAX_POLL_TIME: Duration = Duration::from_secs(40);
+
+/// The result of polling a tournament. `changed` is false if the tournament is identical to the
+/// previous poll, in which case it doesn't need to be processed again.
+#[derive(Clone, Debug)]
+pub struct TournamentPoll {
+    pub tournament: Tournament,
+    pub changed: bool,
+}
+
+impl TournamentPoll {
+    pub fn changed(tournament: Tournament) -> Self {
+        Self {
+            tournament,
+            changed: true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct PollerState {
+    validators: CacheValidators,
+    tournament: Option<Tournament>,
+}
+
+/// Repeatedly fetches a tournament from sendou.ink, using conditional requests and a hash of the
+/// payload to avoid re-parsing a tournament that hasn't changed. If the client saves diagnostics,
+/// tournaments that can't be parsed are skipped, and the last one that could be is returned
+/// instead.
+pub struct TournamentPoller<'a> {
+    client: &'a SendouClient,
+    tournament_id: SendouId,
+    state: Mutex<PollerState>,
+}
+
+impl<'a> TournamentPoller<'a> {
+    pub fn new(client: &'a SendouClient, tournament_id: SendouId) -> Self {
+        Self {
+            client,
+            tournament_id,
+            state: Mutex::new(PollerState::default()),
+        }
+    }
+
+    pub async fn poll(&self) -> Result<TournamentPoll> {
+        let mut validators = self.state.lock().unwrap().validators.clone();
+        let result = self
+            .client
+            .tournament(self.tournament_id, &mut validators)
+            .await;
+
+        let mut state = self.state.lock().unwrap();
+        match (result, &state.tournament) {
+            (Ok(Some(tournament)), _) => {
+                state.validators = validators;
+                state.tournament = Some(tournament.clone());
+                Ok(TournamentPoll::changed(tournament))
+            }
+            (Ok(None), Some(tournament)) => {
+                let tournament = tournament.clone();
+                state.validators = validators;
+                Ok(TournamentPoll {
+                    tournament,
+                    changed: false,
+                })
+            }
+            (Ok(None), None) => {
+                Err("sendou.ink responded 304 Not Modified before sending a tournament".into())
+            }
+            (
+                Err(
+                    err @ Error {
+                        error: ErrorKind::JsonSerialization(_),
+                        ..
+                    },
+                ),
+                Some(tournament),
+            ) if self.client.has_diagnostics() => {
+                println!(
+                    "Failed to parse tournament from sendou.ink, continuing with the last one that could be: {err}"
+                );
+                let tournament = tournament.clone();
+                state.validators = validators;
+                Ok(TournamentPoll {
+                    tournament,
+                    changed: false,
+                })
+            }
+            (Err(err), _) => Err(err),
+        }
+    }
+
+    /// Fetches the current tournament like [`Self::poll`], but falls back to the last tournament
+    /// that could be fetched and parsed if it fails for any reason
+    pub async fn latest(&self) -> Result<Tournament> {
+        match self.poll().await {
+            Ok(poll) => Ok(poll.tournament),
+            Err(err) => {
+                let Some(tournament) = self.state.lock().unwrap().tournament.clone() else {
+                    return Err(err);
+                };
+                println!(
+                    "Failed to fetch tournament from sendou.ink, continuing with the last one that could be: {err}"
+                );
+                Ok(tournament)
+            }
+        }
+    }
+}
+
+/// Decides how long to wait before polling again. Polls quickly while sets are ready or being
+/// played, and backs off while the tournament is idle and unchanged.
+#[derive(Debug, Default)]
+pub struct PollSchedule {
+    unchanged_polls: u32,
+    last_delay: Option<Duration>,
+}
+
+impl PollSchedule {
+    pub fn next_delay(&mut self, poll: &TournamentPoll) -> Duration {
+        if poll.changed {
+            self.unchanged_polls = 0;
+        } else {
+            self.unchanged_polls = self.unchanged_polls.saturating_add(1);
+        }
+        // Sets that are ready can be played and reported at any time, even if no one has started
+        // reporting maps yet
+        let playing = poll.tournament.data.matches.iter().any(|x| match x.status {
+            TournamentMatchStatus::Running => true,
+            TournamentMatchStatus::Ready => x.opponent1.is_some() && x.opponent2.is_some(),
+            _ => false,
+        });
+        if playing {
+            ACTIVE_POLL_TIME
+        } else {
+            IDLE_POLL_TIME
+                .saturating_mul(1 << self.unchanged_polls.min(8))
+                .min(MAX_POLL_TIME)
+        }
+    }
+
+    /// Like [`Self::next_delay`], but also describes the delay if it differs from the previous one
+    pub fn next_delay_logged(&mut self, poll: &TournamentPoll) -> (Duration, Option<String>) {
+        let delay = self.next_delay(poll);
+        if self.last_delay == Some(delay) {
+            return (delay, None);
+        }
+        self.last_delay = Some(delay);
+        let reason = if delay == ACTIVE_POLL_TIME {
+            "sets are in progress".to_string()
+        } else if self.unchanged_polls == 0 {
+            "no sets are in progress".to_string()
+        } else {
+            format!("unchanged for {} polls", self.unchanged_polls)
+        };
+        (
+            delay,
+            Some(format!(
+                "Polling sendou.ink every {}s ({reason})",
+                delay.as_secs()
+            )),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sendou::mock_server::{
+        MockResponse, MockSendouServer, TOURNAMENT_FIXTURE, temp_path,
+    };
+    use crate::sendou::polling::{
+        ACTIVE_POLL_TIME, IDLE_POLL_TIME, MAX_POLL_TIME, PollSchedule, TournamentPoller,
+    };
+    use crate::sendou::recorder::SnapshotRecorder;
+    use crate::sendou::schema::TournamentMatchStatus;
+    use std::fs;
+    use std::sync::Arc;
+
+    const PATH: &str = "/to/1234/register.data?_routes=features/tournament/routes/to.$id";
+
+    #[tokio::test]
+    async fn poller_test() {
+        let server = MockSendouServer::with_fixtures();
+        let client = server.client();
+        let poller = TournamentPoller::new(&client, 1234);
+
+        let first = poller.poll().await.unwrap();
+        assert!(first.changed);
+        let second = poller.poll().await.unwrap();
+        assert!(!second.changed);
+        assert_eq!(second.tournament.context.id, 1234);
+
+        server.queue(
+            PATH,
+            MockResponse::ok(TOURNAMENT_FIXTURE)
+                .with_header("ETag", r#""v2""#)
+                .with_header("Last-Modified", "Sat, 17 Oct 2026 12:00:00 GMT"),
+        );
+        assert!(!poller.poll().await.unwrap().changed);
+        server.queue(PATH, MockResponse::status(304));
+        assert!(!poller.poll().await.unwrap().changed);
+        let requests = server.requests();
+        let conditional = requests.last().unwrap();
+        assert_eq!(conditional.header("if-none-match"), Some(r#""v2""#));
+        assert_eq!(
+            conditional.header("if-modified-since"),
+            Some("Sat, 17 Oct 2026 12:00:00 GMT")
+        );
+
+        let mut schedule = PollSchedule::default();
+        assert_eq!(schedule.next_delay(&first), ACTIVE_POLL_TIME);
+        let mut ready = second.clone();
+        for tourney_match in &mut ready.tournament.data.matches {
+            tourney_match.status = TournamentMatchStatus::Completed;
+        }
+        ready.tournament.data.matches[0].status = TournamentMatchStatus::Ready;
+        assert_eq!(schedule.next_delay(&ready), ACTIVE_POLL_TIME);
+        let mut idle = ready.clone();
+        idle.tournament.data.matches[0].opponent2 = None;
+        assert_eq!(schedule.next_delay(&idle), IDLE_POLL_TIME * 4);
+        assert_eq!(schedule.next_delay(&idle), MAX_POLL_TIME);
+        assert_eq!(schedule.next_delay(&first), ACTIVE_POLL_TIME);
+    }
+
+    #[tokio::test]
+    async fn diagnostics_test() {
+        let invalid = String::from_utf8(TOURNAMENT_FIXTURE.to_vec())
+            .unwrap()
+            .replacen(r#"\"status\": 4"#, r#"\"status\": 9"#, 1);
+        let server = MockSendouServer::with_fixtures();
+        let client = server.client();
+
+        server.queue(PATH, MockResponse::ok(invalid.clone()));
+        let err = TournamentPoller::new(&client, 1234)
+            .poll()
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("features/tournament/routes/to.$id.data: tournament.data.match[0].status"),
+            "{err}"
+        );
+
+        let dir = temp_path("diagnostics");
+        let client = client.with_diagnostics(Some(Arc::new(SnapshotRecorder::new(&dir).unwrap())));
+        let poller = TournamentPoller::new(&client, 1234);
+        assert!(poller.poll().await.unwrap().changed);
+        server.queue(PATH, MockResponse::ok(invalid.clone()));
+        server.queue(PATH, MockResponse::ok(invalid.clone()));
+        let poll = poller.poll().await.unwrap();
+        assert!(!poll.changed);
+        assert_eq!(
+            poll.tournament.data.matches[0].status,
+            TournamentMatchStatus::Completed
+        );
+        assert!(!poller.poll().await.unwrap().changed);
+        assert!(poller.poll().await.unwrap().changed);
+
+        server.queue(PATH, MockResponse::ok(invalid));
+        assert_eq!(poller.latest().await.unwrap().context.id, 1234);
+        server.queue(PATH, MockResponse::status(404));
+        assert_eq!(poller.latest().await.unwrap().context.id, 1234);
+
+        let saved = fs::read_dir(&dir).unwrap().collect::<Vec<_>>();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(saved.len(), 2);
+    }
+}
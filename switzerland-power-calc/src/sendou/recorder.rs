@@ -0,0 +1,75 @@
+use crate::Result;
+use crate::sendou::SendouId;
+use chrono::Utc;
+use itertools::Itertools;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// Archives the raw responses fetched from sendou.ink, so that a tournament can be audited or
+/// replayed later. Tournament snapshots are named `tournament-<timestamp>.json` and are only
+/// written when they differ from the previous snapshot. Match results are named
+/// `match-<id>-<timestamp>.json`.
+pub struct SnapshotRecorder {
+    dir: PathBuf,
+    last_tournament: Mutex<Vec<u8>>,
+}
+
+impl SnapshotRecorder {
+    pub fn new(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            last_tournament: Mutex::new(vec![]),
+        })
+    }
+
+    pub fn record_tournament(&self, payload: &[u8]) {
+        let mut last_tournament = self.last_tournament.lock().unwrap();
+        if *last_tournament == payload {
+            return;
+        }
+        if let Err(err) = self.write("tournament", payload) {
+            println!("Failed to record tournament snapshot: {err}");
+            return;
+        }
+        last_tournament.clear();
+        last_tournament.extend_from_slice(payload);
+    }
+
+    pub fn record_match(&self, match_id: SendouId, payload: &[u8]) {
+        if let Err(err) = self.write(&format!("match-{match_id}"), payload) {
+            println!("Failed to record results for match {match_id}: {err}");
+        }
+    }
+
+    /// Saves a response that couldn't be parsed as `invalid-<name>-<timestamp>.json`, returning
+    /// the path it was saved to
+    pub fn record_invalid(&self, name: &str, payload: &[u8]) -> Result<PathBuf> {
+        self.write(&format!("invalid-{name}"), payload)
+    }
+
+    fn write(&self, prefix: &str, payload: &[u8]) -> Result<PathBuf> {
+        let timestamp = Utc::now().format(TIMESTAMP_FORMAT);
+        let path = self.dir.join(format!("{prefix}-{timestamp}.json"));
+        fs::write(&path, payload)?;
+        Ok(path)
+    }
+}
+
+/// Finds the most recent snapshot in `dir` written by a [`SnapshotRecorder`] with the given prefix
+pub fn latest_snapshot(dir: &Path, prefix: &str) -> Result<Option<PathBuf>> {
+    let prefix = format!("{prefix}-");
+    Ok(fs::read_dir(dir)?
+        .map_ok(|entry| entry.path())
+        .filter_ok(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|timestamp| timestamp.strip_suffix(".json"))
+                .is_some_and(|timestamp| timestamp.starts_with(|c: char| c.is_ascii_digit()))
+        })
+        .process_results(|paths| paths.max())?)
+}
@@ -0,0 +1,123 @@
+use crate::Result;
+use crate::db::Database;
+use crate::sendou::output::ConsoleOutput;
+use crate::sendou::output::TournamentOutput;
+use crate::sendou::polling::TournamentPoll;
+use crate::sendou::recorder::latest_snapshot;
+use crate::sendou::run_state::RunStateFile;
+use crate::sendou::schema::{ToResponse, Tournament};
+use crate::sendou::turbo_stream::TurboStreamed;
+use crate::sendou::{
+    MatchResultsSource, SinkOptions, finalize_tournament, initialize_teams, run_tournament,
+};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+#[tokio::main]
+pub async fn replay_cli(
+    in_db: &Path,
+    out_db: &Path,
+    tournament_path: &Path,
+    matches_dir: Option<&Path>,
+    notify_dir: Option<&Path>,
+) -> Result<()> {
+    if let Some(parent) = out_db.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tournament_path = if tournament_path.is_dir() {
+        &latest_snapshot(tournament_path, "tournament")?.ok_or_else(|| {
+            format!(
+                "No recorded tournament snapshots in {}",
+                tournament_path.display()
+            )
+        })?
+    } else {
+        tournament_path
+    };
+    let tournament = read_saved_tournament(tournament_path)?;
+    let poll_tournament = async || -> Result<_> { Ok(TournamentPoll::changed(tournament.clone())) };
+
+    let old_db = Database::read(in_db)?;
+    let old_players = old_db.clone().into_map();
+    let mut new_players = old_players.clone();
+
+    let (teams, mut record) =
+        initialize_teams(&old_db.config, &tournament, &mut new_players, None).await?;
+    let run_state = Arc::new(RunStateFile::in_memory(tournament.context.id));
+    let sinks = SinkOptions {
+        dir: notify_dir,
+        ..Default::default()
+    };
+    let notifier = if !sinks.is_empty() {
+        Some(sinks.create_notifier(run_state.clone()).await?)
+    } else {
+        None
+    };
+    let output = ConsoleOutput::new(notifier);
+    run_tournament(
+        &MatchResultsSource::Saved(
+            matches_dir
+                .unwrap_or_else(|| tournament_path.parent().unwrap_or(Path::new(".")))
+                .to_path_buf(),
+        ),
+        &output,
+        &mut new_players,
+        &teams,
+        &mut record,
+        &old_db.config,
+        &run_state,
+        &poll_tournament,
+        false,
+    )
+    .await?;
+    output.finish().await?;
+
+    finalize_tournament(out_db, &old_db, &old_players, new_players, record)?;
+    Ok(())
+}
+
+/// Reads a saved `register.data` response, as returned by sendou.ink
+pub fn read_saved_tournament(path: &Path) -> Result<Tournament> {
+    Ok(
+        serde_json::from_reader::<_, TurboStreamed<ToResponse>>(fs::File::open(path)?)?
+            .0
+            .to
+            .data
+            .tournament,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sendou::MatchResultsSource;
+    use crate::sendou::mock_server::{MATCH_FIXTURE, temp_path};
+    use std::fs;
+
+    #[tokio::test]
+    async fn saved_match_results_test() {
+        let dir = temp_path("replay");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("match-11.json"), MATCH_FIXTURE).unwrap();
+        fs::write(
+            dir.join("match-12-20260101T000000.000Z.json"),
+            MATCH_FIXTURE,
+        )
+        .unwrap();
+
+        let source = MatchResultsSource::Saved(dir.clone());
+        for match_id in [11, 12] {
+            let results = source.get(1234, match_id).await.unwrap();
+            assert_eq!(
+                results
+                    .iter()
+                    .map(|result| result.winner_team_id)
+                    .collect::<Vec<_>>(),
+                [1, 4, 1]
+            );
+        }
+        assert!(source.get(1234, 21).await.is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,288 @@
+use crate::Result;
+use crate::sendou::lang::Language;
+use crate::sendou::schema::SendouId;
+use crate::sendou::types::DiscordChannelsMap;
+use serde::{Deserialize, Serialize};
+use serenity::all::{MessageId, UserId};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// What a `sendou` run has already done, so that it can carry on where it left off if it's
+/// restarted instead of sending players duplicate or missing messages
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct RunState {
+    pub tournament_id: SendouId,
+    /// The sets whose results have been processed
+    #[serde(default)]
+    pub processed_matches: BTreeSet<SendouId>,
+    /// The sets skipped with the `skip` command
+    #[serde(default)]
+    pub skipped_matches: BTreeSet<SendouId>,
+    /// Progress messages by [`progress_message_key`]
+    #[serde(default)]
+    pub progress_messages: BTreeMap<String, ProgressMessage>,
+    #[serde(default)]
+    pub channels: DiscordChannelsMap,
+    /// Languages chosen with the language command
+    #[serde(default)]
+    pub languages: HashMap<UserId, Language>,
+}
+
+/// A progress message that's been queued to be sent
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ProgressMessage {
+    /// The names of the notification sinks that haven't confirmed receiving it yet
+    #[serde(default)]
+    pub pending_sinks: BTreeSet<String>,
+    /// The ID of the message once it's been sent on Discord
+    #[serde(default)]
+    pub discord_message: Option<MessageId>,
+}
+
+/// A [`RunState`] that's saved to a file whenever it changes
+pub struct RunStateFile {
+    path: Option<PathBuf>,
+    state: Mutex<RunState>,
+    /// Progress messages that were queued but never confirmed sent before the run was restarted
+    unsent_messages: Mutex<HashSet<String>>,
+}
+
+impl RunStateFile {
+    /// Loads the state left in `path` by a previous run of the same tournament, if there is one
+    pub fn load(path: &Path, tournament_id: SendouId) -> Result<Self> {
+        let fresh_state = RunState {
+            tournament_id,
+            ..Default::default()
+        };
+        let state = match fs::read(path) {
+            Ok(contents) => {
+                let state = serde_json::from_slice::<RunState>(&contents)?;
+                if state.tournament_id == tournament_id {
+                    println!("Resuming run from {}", path.display());
+                    state
+                } else {
+                    println!(
+                        "Ignoring run state in {} for tournament {}",
+                        path.display(),
+                        state.tournament_id
+                    );
+                    fresh_state
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => fresh_state,
+            Err(err) => return Err(err.into()),
+        };
+        let unsent_messages = state
+            .progress_messages
+            .iter()
+            .filter(|(_, message)| !message.pending_sinks.is_empty())
+            .map(|(key, _)| key.clone())
+            .collect();
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            state: Mutex::new(state),
+            unsent_messages: Mutex::new(unsent_messages),
+        })
+    }
+
+    /// The tournament whose run left its state in `path` unfinished, if any
+    pub fn pending_tournament(path: &Path) -> Result<Option<SendouId>> {
+        match fs::read(path) {
+            Ok(contents) => Ok(Some(
+                serde_json::from_slice::<RunState>(&contents)?.tournament_id,
+            )),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// A state that isn't saved anywhere
+    pub fn in_memory(tournament_id: SendouId) -> Self {
+        Self {
+            path: None,
+            state: Mutex::new(RunState {
+                tournament_id,
+                ..Default::default()
+            }),
+            unsent_messages: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&RunState) -> R) -> R {
+        f(&self.state.lock().unwrap())
+    }
+
+    /// Changes the state and saves it
+    pub fn update<R>(&self, f: impl FnOnce(&mut RunState) -> R) -> Result<R> {
+        let mut state = self.state.lock().unwrap();
+        let result = f(&mut state);
+        self.save(&state)?;
+        Ok(result)
+    }
+
+    fn save(&self, state: &RunState) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        // Replace the file in one go, so that a crash while saving can't leave it half-written
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(state)?)?;
+        fs::rename(temp_path, path)?;
+        Ok(())
+    }
+
+    /// Which of `sinks` the progress message with `key` should be sent to. Messages are sent to
+    /// each sink once, unless the sink never confirmed receiving it before the run was restarted.
+    pub fn sinks_to_notify<'a>(&self, key: &str, sinks: &[&'a str]) -> Vec<&'a str> {
+        let resumed = self.unsent_messages.lock().unwrap().remove(key);
+        self.read(|state| match state.progress_messages.get(key) {
+            None => sinks.to_vec(),
+            Some(message) if resumed => sinks
+                .iter()
+                .copied()
+                .filter(|sink| message.pending_sinks.contains(*sink))
+                .collect(),
+            Some(_) => vec![],
+        })
+    }
+
+    /// Records that the progress message with `key` is about to be sent to `sinks`
+    pub fn queue_message(&self, key: &str, sinks: &[&str]) -> Result<()> {
+        self.update(|state| {
+            state
+                .progress_messages
+                .entry(key.to_string())
+                .or_default()
+                .pending_sinks = sinks.iter().map(|sink| sink.to_string()).collect();
+        })
+    }
+
+    /// Records that `sink` has received the progress message with `key`
+    pub fn message_delivered(
+        &self,
+        key: &str,
+        sink: &str,
+        discord_message: Option<MessageId>,
+    ) -> Result<()> {
+        self.update(|state| {
+            if let Some(message) = state.progress_messages.get_mut(key) {
+                message.pending_sinks.remove(sink);
+                if discord_message.is_some() {
+                    message.discord_message = discord_message;
+                }
+            }
+        })
+    }
+
+    /// Deletes the state file once the run has finished
+    pub fn remove(&self) -> Result<()> {
+        match &self.path {
+            Some(path) => match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+                _ => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn progress_message_key(set_id: SendouId, team_id: SendouId, player_id: SendouId) -> String {
+    format!("set-{set_id}-{team_id}-{player_id}")
+}
+
+/// The prefix of the keys of every progress message sent for a set
+pub fn progress_message_prefix(set_id: SendouId) -> String {
+    format!("set-{set_id}-")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sendou::lang::Language;
+    use crate::sendou::mock_server::temp_path;
+    use crate::sendou::run_state::{RunStateFile, progress_message_key};
+    use serenity::all::{ChannelId, MessageId, UserId};
+    use std::fs;
+
+    #[test]
+    fn resume_test() {
+        let path = temp_path("run-state").with_extension("json");
+        let sent = progress_message_key(11, 1, 101);
+        let unsent = progress_message_key(11, 4, 104);
+
+        let state = RunStateFile::load(&path, 1234).unwrap();
+        assert_eq!(state.sinks_to_notify(&sent, &["discord"]), ["discord"]);
+        state.queue_message(&sent, &["discord"]).unwrap();
+        state.queue_message(&unsent, &["discord"]).unwrap();
+        state
+            .update(|state| {
+                state.processed_matches.insert(11);
+                state.skipped_matches.insert(12);
+                state.channels.insert(1, ChannelId::new(5));
+                state
+                    .languages
+                    .insert(UserId::new(900), Language::EnglishUnitedStates);
+            })
+            .unwrap();
+        assert!(state.sinks_to_notify(&unsent, &["discord"]).is_empty());
+        state
+            .message_delivered(&sent, "discord", Some(MessageId::new(77)))
+            .unwrap();
+
+        assert_eq!(RunStateFile::pending_tournament(&path).unwrap(), Some(1234));
+        let resumed = RunStateFile::load(&path, 1234).unwrap();
+        resumed.read(|state| {
+            assert!(state.processed_matches.contains(&11));
+            assert!(state.skipped_matches.contains(&12));
+            assert_eq!(state.channels[&1], ChannelId::new(5));
+            assert_eq!(state.languages.len(), 1);
+            assert_eq!(
+                state.progress_messages[&sent].discord_message,
+                Some(MessageId::new(77))
+            );
+        });
+        assert!(resumed.sinks_to_notify(&sent, &["discord"]).is_empty());
+        assert_eq!(resumed.sinks_to_notify(&unsent, &["discord"]), ["discord"]);
+        assert!(resumed.sinks_to_notify(&unsent, &["discord"]).is_empty());
+
+        let other_tournament = RunStateFile::load(&path, 5678).unwrap();
+        assert!(other_tournament.read(|state| state.processed_matches.is_empty()));
+
+        resumed.remove().unwrap();
+        assert!(!fs::exists(&path).unwrap());
+        assert_eq!(RunStateFile::pending_tournament(&path).unwrap(), None);
+        resumed.remove().unwrap();
+    }
+
+    #[test]
+    fn failed_sink_test() {
+        let path = temp_path("run-state-sink").with_extension("json");
+        let key = progress_message_key(12, 1, 101);
+        let sinks = ["discord", "directory", "webhook"];
+
+        let state = RunStateFile::load(&path, 1234).unwrap();
+        assert_eq!(state.sinks_to_notify(&key, &sinks), sinks);
+        state.queue_message(&key, &sinks).unwrap();
+        state
+            .message_delivered(&key, "discord", Some(MessageId::new(78)))
+            .unwrap();
+        state.message_delivered(&key, "directory", None).unwrap();
+
+        let resumed = RunStateFile::load(&path, 1234).unwrap();
+        let retry = resumed.sinks_to_notify(&key, &sinks);
+        assert_eq!(retry, ["webhook"]);
+        resumed.queue_message(&key, &retry).unwrap();
+        resumed.message_delivered(&key, "webhook", None).unwrap();
+        resumed.read(|state| {
+            let message = &state.progress_messages[&key];
+            assert!(message.pending_sinks.is_empty());
+            assert_eq!(message.discord_message, Some(MessageId::new(78)));
+        });
+
+        let finished = RunStateFile::load(&path, 1234).unwrap();
+        assert!(finished.sinks_to_notify(&key, &sinks).is_empty());
+        finished.remove().unwrap();
+    }
+}
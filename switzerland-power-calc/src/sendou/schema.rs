@@ -1,8 +1,9 @@
+use crate::db::{PlayerId, SetResult};
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer};
 use serde_repr::Deserialize_repr;
-use serde_with::DefaultOnNull;
-use serde_with::{BoolFromInt, json::JsonString, serde_as};
+use serde_with::{BoolFromInt, DefaultOnNull, DeserializeAs, serde_as};
 
 pub type SendouId = u32;
 
@@ -15,10 +16,30 @@ pub struct ToResponse {
 #[serde_as]
 #[derive(Clone, Debug, Deserialize)]
 pub struct TournamentDataWrapper {
-    #[serde_as(as = "JsonString")]
+    #[serde_as(as = "JsonStringWithPath")]
     pub data: TournamentRoot,
 }
 
+/// Like [`serde_with::json::JsonString`], but errors include the path to the field that couldn't
+/// be deserialized
+pub struct JsonStringWithPath;
+
+impl<'de, T: DeserializeOwned> DeserializeAs<'de, T> for JsonStringWithPath {
+    fn deserialize_as<D>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let json = String::deserialize(deserializer)?;
+        serde_json::from_str(&json).or_else(|_| {
+            let mut json_deserializer = serde_json::Deserializer::from_str(&json);
+            let parsed =
+                serde_path_to_error::deserialize(&mut json_deserializer).map_err(Error::custom)?;
+            json_deserializer.end().map_err(Error::custom)?;
+            Ok(parsed)
+        })
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct TournamentRoot {
     pub tournament: Tournament,
@@ -52,12 +73,105 @@ pub struct TournamentStage {
 }
 
 #[derive(Copy, Clone, Debug, Deserialize)]
-#[serde(tag = "type", content = "settings", rename_all = "snake_case")]
+#[serde(
+    tag = "type",
+    content = "settings",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
 pub enum TournamentStageSettings {
     SingleElimination {},
     DoubleElimination {},
-    RoundRobin {},
-    Swiss {},
+    RoundRobin {
+        #[serde(default)]
+        group_count: Option<u32>,
+        /// The total number of teams in the stage
+        #[serde(default)]
+        size: Option<u32>,
+        #[serde(default)]
+        round_robin_mode: Option<RoundRobinMode>,
+    },
+    Swiss {
+        #[serde(default)]
+        swiss: Option<SwissSettings>,
+    },
+}
+
+impl TournamentStageSettings {
+    pub fn group_count(&self) -> Option<u32> {
+        match self {
+            Self::RoundRobin { group_count, .. } => *group_count,
+            Self::Swiss { swiss } => swiss.map(|x| x.group_count),
+            _ => None,
+        }
+    }
+
+    pub fn teams_per_group(&self) -> Option<u32> {
+        match self {
+            Self::RoundRobin {
+                group_count: Some(group_count),
+                size: Some(size),
+                ..
+            } if *group_count > 0 => Some(size.div_ceil(*group_count)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundRobinMode {
+    Simple,
+    Double,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwissSettings {
+    pub group_count: u32,
+    pub round_count: u32,
+}
+
+impl TournamentData {
+    /// The stage and group a round belongs to
+    pub fn round_location(
+        &self,
+        round_id: SendouId,
+    ) -> Option<(&TournamentStage, &TournamentGroup)> {
+        let round = self.rounds.iter().find(|x| x.id == round_id)?;
+        let group = self.groups.iter().find(|x| x.id == round.group_id)?;
+        let stage = self.stages.iter().find(|x| x.id == group.stage_id)?;
+        Some((stage, group))
+    }
+
+    /// The number of a round within its group and the total number of rounds the group will have.
+    /// Swiss rounds are created as the stage progresses, so their total comes from the stage
+    /// settings.
+    pub fn round_progress(&self, round_id: SendouId) -> Option<(u32, u32)> {
+        let round = self.rounds.iter().find(|x| x.id == round_id)?;
+        let (stage, group) = self.round_location(round_id)?;
+        let total = match stage.settings {
+            TournamentStageSettings::Swiss {
+                swiss: Some(swiss), ..
+            } => swiss.round_count,
+            TournamentStageSettings::RoundRobin {
+                round_robin_mode, ..
+            } if let Some(teams) = stage.settings.teams_per_group() => {
+                let rounds = if teams % 2 == 0 { teams - 1 } else { teams };
+                match round_robin_mode {
+                    Some(RoundRobinMode::Double) => rounds * 2,
+                    _ => rounds,
+                }
+            }
+            _ => self
+                .rounds
+                .iter()
+                .filter(|x| x.group_id == group.id)
+                .map(|x| x.number)
+                .max()?,
+        };
+        Some((round.number, total))
+    }
 }
 
 #[derive(Copy, Clone, Debug, Deserialize)]
@@ -113,6 +227,15 @@ pub enum TournamentMatchResult {
     Loss,
 }
 
+impl From<TournamentMatchResult> for SetResult {
+    fn from(value: TournamentMatchResult) -> Self {
+        match value {
+            TournamentMatchResult::Win => SetResult::Win,
+            TournamentMatchResult::Loss => SetResult::Loss,
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Deserialize_repr)]
 #[repr(u8)]
 pub enum TournamentMatchStatus {
@@ -148,6 +271,23 @@ pub struct TournamentTeam {
     pub avg_seeding_skill_ordinal: f64,
 }
 
+impl TournamentTeam {
+    pub fn player_ids(&self) -> Vec<PlayerId> {
+        self.members
+            .iter()
+            .map(|member| PlayerId::Sendou(member.user_id))
+            .collect()
+    }
+
+    /// The name to show for the team: the player's username for solo teams, or the team name
+    pub fn display_name(&self) -> &str {
+        match self.members.as_slice() {
+            [member] => &member.username,
+            _ => &self.name,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TournamentTeamMember {
@@ -0,0 +1,189 @@
+use crate::sendou::schema::{
+    SendouId, TournamentData, TournamentMatchResult, TournamentMatchStatus,
+};
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+
+/// A team's record in a round robin or Swiss stage
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Standing {
+    pub team_id: SendouId,
+    pub wins: u32,
+    pub losses: u32,
+    pub map_wins: u32,
+    pub map_losses: u32,
+    /// The average set win rate of every opponent the team played
+    pub opponent_win_rate: f64,
+}
+
+impl Standing {
+    pub fn map_differential(&self) -> i64 {
+        self.map_wins as i64 - self.map_losses as i64
+    }
+
+    fn win_rate(&self) -> f64 {
+        let sets = self.wins + self.losses;
+        if sets == 0 {
+            0.0
+        } else {
+            self.wins as f64 / sets as f64
+        }
+    }
+}
+
+/// Computes the standings of a group from its completed sets, ordered by wins, then losses, then map
+/// differential, then opponent win rate
+pub fn compute_standings(tournament_data: &TournamentData, group_id: SendouId) -> Vec<Standing> {
+    let rounds = tournament_data
+        .rounds
+        .iter()
+        .filter(|x| x.group_id == group_id)
+        .map(|x| x.id)
+        .collect::<HashSet<_>>();
+
+    let mut standings = HashMap::new();
+    let mut opponents = HashMap::<_, Vec<_>>::new();
+    for tourney_match in tournament_data
+        .matches
+        .iter()
+        .filter(|x| rounds.contains(&x.round_id))
+    {
+        let (Some(opponent1), Some(opponent2)) = (tourney_match.opponent1, tourney_match.opponent2)
+        else {
+            continue; // BYE
+        };
+        let (Some(team1), Some(team2)) = (opponent1.id, opponent2.id) else {
+            continue;
+        };
+        for team_id in [team1, team2] {
+            standings.entry(team_id).or_insert_with(|| Standing {
+                team_id,
+                ..Default::default()
+            });
+        }
+        if tourney_match.status != TournamentMatchStatus::Completed {
+            continue;
+        }
+        let Some(result1) = opponent1.result else {
+            continue;
+        };
+        let team1_won = result1 == TournamentMatchResult::Win;
+        for (team_id, opponent_id, score, opponent_score, won) in [
+            (team1, team2, opponent1.score, opponent2.score, team1_won),
+            (team2, team1, opponent2.score, opponent1.score, !team1_won),
+        ] {
+            let standing = standings.get_mut(&team_id).unwrap();
+            if won {
+                standing.wins += 1;
+            } else {
+                standing.losses += 1;
+            }
+            standing.map_wins += score;
+            standing.map_losses += opponent_score;
+            opponents.entry(team_id).or_default().push(opponent_id);
+        }
+    }
+
+    let win_rates = standings
+        .iter()
+        .map(|(id, standing)| (*id, standing.win_rate()))
+        .collect::<HashMap<_, _>>();
+    for (team_id, standing) in &mut standings {
+        if let Some(opponents) = opponents.get(team_id) {
+            standing.opponent_win_rate =
+                opponents.iter().map(|x| win_rates[x]).sum::<f64>() / opponents.len() as f64;
+        }
+    }
+
+    standings
+        .into_values()
+        .sorted_by(|a, b| {
+            b.wins
+                .cmp(&a.wins)
+                .then(a.losses.cmp(&b.losses))
+                .then(b.map_differential().cmp(&a.map_differential()))
+                .then(b.opponent_win_rate.total_cmp(&a.opponent_win_rate))
+                .then(a.team_id.cmp(&b.team_id))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sendou::schema::{
+        TournamentData, TournamentGroup, TournamentMatch, TournamentMatchOpponent,
+        TournamentMatchResult, TournamentMatchStatus, TournamentRound, TournamentRoundMaps,
+        TournamentRoundMapsMatchType, TournamentStage, TournamentStageSettings,
+    };
+    use crate::sendou::standings::compute_standings;
+
+    #[test]
+    fn round_robin_standings_test() {
+        let opponent = |id, score, won: bool| TournamentMatchOpponent {
+            id: Some(id),
+            score,
+            result: Some(if won {
+                TournamentMatchResult::Win
+            } else {
+                TournamentMatchResult::Loss
+            }),
+        };
+        let set = |id, team1, score1, team2, score2| TournamentMatch {
+            id,
+            opponent1: Some(opponent(team1, score1, score1 > score2)),
+            opponent2: Some(opponent(team2, score2, score2 > score1)),
+            round_id: 1,
+            status: TournamentMatchStatus::Completed,
+        };
+        let data = TournamentData {
+            stages: vec![TournamentStage {
+                id: 1,
+                name: "Groups".to_string(),
+                settings: TournamentStageSettings::RoundRobin {
+                    group_count: Some(1),
+                    size: Some(4),
+                    round_robin_mode: None,
+                },
+            }],
+            groups: vec![TournamentGroup {
+                id: 1,
+                number: 1,
+                stage_id: 1,
+            }],
+            rounds: vec![TournamentRound {
+                group_id: 1,
+                id: 1,
+                number: 1,
+                maps: TournamentRoundMaps {
+                    count: 3,
+                    match_type: TournamentRoundMapsMatchType::BestOf,
+                },
+            }],
+            matches: vec![
+                set(1, 10, 2, 20, 0),
+                set(2, 30, 2, 40, 0),
+                set(3, 10, 2, 30, 1),
+                set(4, 20, 2, 40, 0),
+                set(5, 40, 2, 10, 0),
+                set(6, 20, 2, 30, 1),
+                TournamentMatch {
+                    status: TournamentMatchStatus::Ready,
+                    ..set(7, 10, 0, 20, 0)
+                },
+            ],
+        };
+
+        let standings = compute_standings(&data, 1);
+        assert_eq!(
+            standings.iter().map(|x| x.team_id).collect::<Vec<_>>(),
+            [10, 20, 30, 40]
+        );
+        assert_eq!((standings[0].wins, standings[0].losses), (2, 1));
+        assert_eq!(standings[0].map_differential(), 1);
+        assert_eq!(standings[1].map_differential(), 1);
+        assert_eq!(standings[2].map_differential(), 0);
+        assert_eq!((standings[3].map_wins, standings[3].map_losses), (2, 4));
+        assert!((standings[0].opponent_win_rate - 4.0 / 9.0).abs() < 1e-9);
+        assert!(compute_standings(&data, 2).is_empty());
+    }
+}
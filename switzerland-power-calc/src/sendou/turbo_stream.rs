@@ -1,66 +1,717 @@
+use chrono::{DateTime, SecondsFormat, Utc};
 use derive_more::Display;
-use serde::de::{DeserializeOwned, Error};
-use serde::{Deserialize, Deserializer};
-use serde_json::{Number, Value};
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{
+    DeserializeOwned, DeserializeSeed, EnumAccess, Error, IgnoredAny, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, forward_to_deserialize_any, ser};
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
 
 #[derive(Debug, Display, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct TurboStreamed<T>(pub T);
 
-// Based on https://github.com/jacob-ebey/turbo-stream/blob/c974fa4af885aeb145e4517e474b6b4079677685/src/unflatten.ts
+// Sentinel indices, from https://github.com/jacob-ebey/turbo-stream/blob/c974fa4af885aeb145e4517e474b6b4079677685/src/utils.ts
+const HOLE: isize = -1;
+const NAN: isize = -2;
+const NEGATIVE_INFINITY: isize = -3;
+const NEGATIVE_ZERO: isize = -4;
+const NULL: isize = -5;
+const POSITIVE_INFINITY: isize = -6;
+const UNDEFINED: isize = -7;
+
 impl<'de, T: DeserializeOwned> Deserialize<'de> for TurboStreamed<T> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let unparsed = Vec::<Value>::deserialize(deserializer)?;
+        let table = Vec::<Value>::deserialize(deserializer)?;
+        let parsed = from_table(&table).map_err(Error::custom)?;
+        Ok(TurboStreamed(parsed))
+    }
+}
+
+/// Deserializes a value directly from a turbo-stream index table, without rebuilding it with
+/// [`unflatten`] first. Strings are borrowed from the table, and ignored fields are skipped without
+/// being visited. Errors include the path to the field that couldn't be deserialized.
+pub fn from_table<'de, T: Deserialize<'de>>(table: &'de [Value]) -> serde_json::Result<T> {
+    let deserializer = TableDeserializer {
+        table,
+        index: 0,
+        depth: 0,
+    };
+    // Tracking the path is slower, so only do it once we know there's an error to report
+    T::deserialize(deserializer)
+        .or_else(|_| serde_path_to_error::deserialize(deserializer).map_err(Error::custom))
+}
+
+// Based on https://github.com/jacob-ebey/turbo-stream/blob/c974fa4af885aeb145e4517e474b6b4079677685/src/unflatten.ts
+/// Rebuilds a value from a turbo-stream index table. JavaScript types that JSON can't represent are
+/// mapped to the closest JSON equivalent:
+/// - `undefined`, holes, `NaN`, and infinities become `null`, like `JSON.stringify`
+/// - `Date`s become RFC 3339 strings
+/// - `BigInt`s become numbers, or strings if they're too large
+/// - `Map`s become objects if all their keys are strings, and arrays of `[key, value]` otherwise
+/// - `Set`s become arrays
+/// - `URL`s, `Symbol`s, and `RegExp`s (as `/source/flags`) become strings
+/// - `Error`s become objects with `name` and `message` fields
+/// - Unresolved `Promise`s become `null`
+pub fn unflatten(table: &[Value]) -> Result<Value, String> {
+    Unflattener {
+        table,
+        in_progress: vec![false; table.len()],
+    }
+    .hydrate(0)
+}
+
+const TYPE_BIGINT: &str = "B";
+const TYPE_DATE: &str = "D";
+const TYPE_ERROR: &str = "E";
+const TYPE_MAP: &str = "M";
+const TYPE_NULL_OBJECT: &str = "N";
+const TYPE_PROMISE: &str = "P";
+const TYPE_REGEXP: &str = "R";
+const TYPE_SET: &str = "S";
+const TYPE_SYMBOL: &str = "Y";
+const TYPE_URL: &str = "U";
+const TYPE_PREVIOUS_RESOLVED: &str = "Z";
+
+struct Unflattener<'a> {
+    table: &'a [Value],
+    /// The indices currently being hydrated, to detect cyclic references
+    in_progress: Vec<bool>,
+}
+
+impl Unflattener<'_> {
+    fn hydrate(&mut self, index: isize) -> Result<Value, String> {
+        if index < 0 {
+            return match index {
+                NEGATIVE_ZERO => Ok(Value::Number(Number::from_f64(-0.0).unwrap())),
+                HOLE | NAN | NEGATIVE_INFINITY | NULL | POSITIVE_INFINITY | UNDEFINED => {
+                    Ok(Value::Null)
+                }
+                unknown => Err(format!("Unknown sentinel index {unknown}")),
+            };
+        }
+        let entry = self
+            .table
+            .get(index as usize)
+            .ok_or_else(|| format!("Index {index} is out of bounds"))?;
+        if std::mem::replace(&mut self.in_progress[index as usize], true) {
+            return Err(format!("Index {index} references itself"));
+        }
+        let result = match entry {
+            Value::Array(values) => match values.first() {
+                Some(Value::String(type_tag)) => self.hydrate_typed(type_tag, &values[1..]),
+                _ => values
+                    .iter()
+                    .map(|x| self.hydrate(index_from_value(x)?))
+                    .collect::<Result<_, _>>()
+                    .map(Value::Array),
+            },
+            Value::Object(values) => values
+                .iter()
+                .map(|(key, value)| {
+                    let key = key
+                        .strip_prefix('_')
+                        .and_then(|x| x.parse().ok())
+                        .ok_or_else(|| format!("Invalid object key {key}"))?;
+                    let key = match self.hydrate(key)? {
+                        Value::String(key) => key,
+                        invalid => return Err(format!("Invalid object key {invalid}")),
+                    };
+                    Ok((key, self.hydrate(index_from_value(value)?)?))
+                })
+                .collect::<Result<_, _>>()
+                .map(Value::Object),
+            primitive => Ok(primitive.clone()),
+        };
+        self.in_progress[index as usize] = false;
+        result
+    }
+
+    fn hydrate_typed(&mut self, type_tag: &str, args: &[Value]) -> Result<Value, String> {
+        let arg = |i: usize| {
+            args.get(i)
+                .ok_or_else(|| format!("Missing argument {i} for type {type_tag}"))
+        };
+        let string_arg = |i: usize| {
+            arg(i)?
+                .as_str()
+                .ok_or_else(|| format!("Expected a string argument for type {type_tag}"))
+        };
+        Ok(match type_tag {
+            TYPE_BIGINT => {
+                let digits = string_arg(0)?;
+                digits
+                    .parse::<i64>()
+                    .map(Value::from)
+                    .or_else(|_| digits.parse::<u64>().map(Value::from))
+                    .unwrap_or_else(|_| Value::String(digits.to_string()))
+            }
+            TYPE_DATE => {
+                let millis = arg(0)?;
+                millis
+                    .as_f64()
+                    .and_then(|x| DateTime::<Utc>::from_timestamp_millis(x as i64))
+                    .map(|x| Value::String(x.to_rfc3339_opts(SecondsFormat::Millis, true)))
+                    .ok_or_else(|| format!("Invalid date {millis}"))?
+            }
+            TYPE_ERROR => {
+                let mut error = Map::new();
+                error.insert(
+                    "name".to_string(),
+                    args.get(1).cloned().unwrap_or_else(|| "Error".into()),
+                );
+                error.insert("message".to_string(), arg(0)?.clone());
+                Value::Object(error)
+            }
+            TYPE_MAP => {
+                let entries = args
+                    .chunks(2)
+                    .map(|entry| match entry {
+                        [key, value] => Ok((
+                            self.hydrate(index_from_value(key)?)?,
+                            self.hydrate(index_from_value(value)?)?,
+                        )),
+                        _ => Err("Map is missing a value".to_string()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if entries.iter().all(|(key, _)| key.is_string()) {
+                    Value::Object(
+                        entries
+                            .into_iter()
+                            .map(|(key, value)| (key.as_str().unwrap().to_string(), value))
+                            .collect(),
+                    )
+                } else {
+                    Value::Array(
+                        entries
+                            .into_iter()
+                            .map(|(key, value)| Value::Array(vec![key, value]))
+                            .collect(),
+                    )
+                }
+            }
+            TYPE_NULL_OBJECT => Value::Object(
+                args.chunks(2)
+                    .map(|entry| match entry {
+                        [Value::String(key), value] => {
+                            Ok((key.clone(), self.hydrate(index_from_value(value)?)?))
+                        }
+                        _ => Err("Invalid null prototype object entry".to_string()),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            TYPE_PROMISE => Value::Null,
+            TYPE_REGEXP => Value::String(format!(
+                "/{}/{}",
+                string_arg(0)?,
+                args.get(1).and_then(Value::as_str).unwrap_or_default()
+            )),
+            TYPE_SET => Value::Array(
+                args.iter()
+                    .map(|x| self.hydrate(index_from_value(x)?))
+                    .collect::<Result<_, _>>()?,
+            ),
+            TYPE_SYMBOL | TYPE_URL => Value::String(string_arg(0)?.to_string()),
+            TYPE_PREVIOUS_RESOLVED => self.hydrate(index_from_value(arg(0)?)?)?,
+            unknown => return Err(format!("Unknown type {unknown}")),
+        })
+    }
+}
+
+fn index_from_value(value: &Value) -> Result<isize, String> {
+    value
+        .as_i64()
+        .and_then(|x| x.try_into().ok())
+        .ok_or_else(|| format!("Invalid index {value}"))
+}
+
+/// How deeply entries may be nested before they're assumed to reference themselves
+const MAX_DEPTH: usize = 512;
+
+#[derive(Copy, Clone)]
+struct TableDeserializer<'de> {
+    table: &'de [Value],
+    index: isize,
+    depth: usize,
+}
+
+enum Resolved<'de> {
+    Sentinel(isize),
+    Entry(&'de Value),
+    /// Typed values are rare, so they're hydrated the same way [`unflatten`] does it
+    Typed(Value),
+}
 
-        fn parse_index_num(x: &Value) -> Result<isize, Value> {
-            let index = x.as_i64().ok_or_else(|| x.clone())?;
-            Ok(index.try_into().map_err(|_| index)?)
+impl<'de> TableDeserializer<'de> {
+    fn child(&self, index: &Value) -> serde_json::Result<Self> {
+        Ok(Self {
+            table: self.table,
+            index: index_from_value(index).map_err(Error::custom)?,
+            depth: self.depth + 1,
+        })
+    }
+
+    /// Looks up the string an object key (`_<index>`) refers to
+    fn key(&self, key: &str) -> serde_json::Result<&'de str> {
+        let index = key
+            .strip_prefix('_')
+            .and_then(|x| x.parse::<usize>().ok())
+            .ok_or_else(|| Error::custom(format_args!("Invalid object key {key}")))?;
+        match self.table.get(index) {
+            Some(Value::String(key)) => Ok(key),
+            _ => Err(Error::custom(format_args!("Invalid object key {key}"))),
         }
+    }
 
-        fn parse_index_str(x: &str) -> Result<isize, Value> {
-            Ok(x.trim_start_matches('_').parse().map_err(|_| x)?)
+    fn resolve(&self) -> serde_json::Result<Resolved<'de>> {
+        if self.index < 0 {
+            return Ok(Resolved::Sentinel(self.index));
         }
+        if self.depth > MAX_DEPTH {
+            return Err(Error::custom(format_args!(
+                "Index {} is nested too deeply or references itself",
+                self.index
+            )));
+        }
+        let entry = self
+            .table
+            .get(self.index as usize)
+            .ok_or_else(|| Error::custom(format_args!("Index {} is out of bounds", self.index)))?;
+        if let Value::Array(values) = entry
+            && let Some(Value::String(_)) = values.first()
+        {
+            return Unflattener {
+                table: self.table,
+                in_progress: vec![false; self.table.len()],
+            }
+            .hydrate(self.index)
+            .map(Resolved::Typed)
+            .map_err(Error::custom);
+        }
+        Ok(Resolved::Entry(entry))
+    }
+}
+
+impl<'de> Deserializer<'de> for TableDeserializer<'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> serde_json::Result<V::Value> {
+        match self.resolve()? {
+            Resolved::Sentinel(NEGATIVE_ZERO) => visitor.visit_f64(-0.0),
+            Resolved::Sentinel(
+                HOLE | NAN | NEGATIVE_INFINITY | NULL | POSITIVE_INFINITY | UNDEFINED,
+            ) => visitor.visit_unit(),
+            Resolved::Sentinel(unknown) => Err(Error::custom(format_args!(
+                "Unknown sentinel index {unknown}"
+            ))),
+            Resolved::Entry(Value::Array(values)) => visitor.visit_seq(TableSeqAccess {
+                parent: self,
+                values: values.iter(),
+            }),
+            Resolved::Entry(Value::Object(values)) => visitor.visit_map(TableMapAccess {
+                parent: self,
+                entries: values.iter(),
+                value: None,
+            }),
+            Resolved::Entry(primitive) => primitive.deserialize_any(visitor),
+            Resolved::Typed(value) => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> serde_json::Result<V::Value> {
+        match self.resolve()? {
+            Resolved::Sentinel(
+                HOLE | NAN | NEGATIVE_INFINITY | NULL | POSITIVE_INFINITY | UNDEFINED,
+            )
+            | Resolved::Entry(Value::Null) => visitor.visit_none(),
+            Resolved::Sentinel(_) | Resolved::Entry(_) => visitor.visit_some(self),
+            Resolved::Typed(value) => value.deserialize_option(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> serde_json::Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
 
-        fn parse_value_from_index(unparsed: &Vec<Value>, index: isize) -> Result<Value, Value> {
-            if index < 0 {
-                return match index {
-                    -4 => Ok(Value::Number(Number::from_f64(-0.0).unwrap())),
-                    -5 | -7 => Ok(Value::Null), // Map undefined and null to null
-                    unsupported => Err(unsupported.into()), // NaN, -inf, +inf, HOLE
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> serde_json::Result<V::Value> {
+        match self.resolve()? {
+            Resolved::Entry(Value::Object(values)) => {
+                let mut entries = values.iter();
+                let (Some((key, value)), None) = (entries.next(), entries.next()) else {
+                    return Err(Error::custom(
+                        "Expected an object with a single key for an enum",
+                    ));
                 };
+                visitor.visit_enum(TableEnumAccess {
+                    variant: self.key(key)?,
+                    value: self.child(value)?,
+                })
             }
-            let parsed_value = match unparsed.get(index as usize).ok_or(index)? {
-                Value::Array(values) => Value::Array(
-                    values
-                        .iter()
-                        .map(|v| parse_value_from_index(unparsed, parse_index_num(v)?))
-                        .collect::<Result<Vec<Value>, Value>>()?,
-                ),
-                Value::Object(values) => Value::Object(
-                    values
-                        .iter()
-                        .map(|(k, v)| {
-                            Ok((
-                                match parse_value_from_index(unparsed, parse_index_str(k)?)? {
-                                    Value::String(s) => s,
-                                    invalid => return Err(invalid),
-                                },
-                                parse_value_from_index(unparsed, parse_index_num(v)?)?,
-                            ))
-                        })
-                        .collect::<Result<serde_json::Map<String, Value>, Value>>()?,
-                ),
-                other => other.clone(),
-            };
-            Ok(parsed_value)
+            Resolved::Entry(value) => value.deserialize_enum(name, variants, visitor),
+            Resolved::Typed(value) => value.deserialize_enum(name, variants, visitor),
+            Resolved::Sentinel(_) => self.deserialize_any(visitor),
         }
+    }
 
-        let value = parse_value_from_index(&unparsed, 0)
-            .map_err(|i| Error::custom(format_args!("Invalid index {i}")))?;
-        let parsed = serde_json::from_value(value)
-            .map_err(|e| Error::custom(format_args!("Failed to parse via JSON: {e}")))?;
-        Ok(TurboStreamed(parsed))
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> serde_json::Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+struct TableSeqAccess<'de> {
+    parent: TableDeserializer<'de>,
+    values: std::slice::Iter<'de, Value>,
+}
+
+impl<'de> SeqAccess<'de> for TableSeqAccess<'de> {
+    type Error = serde_json::Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> serde_json::Result<Option<S::Value>> {
+        self.values
+            .next()
+            .map(|x| seed.deserialize(self.parent.child(x)?))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+struct TableMapAccess<'de> {
+    parent: TableDeserializer<'de>,
+    entries: serde_json::map::Iter<'de>,
+    value: Option<&'de Value>,
+}
+
+impl<'de> MapAccess<'de> for TableMapAccess<'de> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> serde_json::Result<Option<S::Value>> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        seed.deserialize(BorrowedStrDeserializer::new(self.parent.key(key)?))
+            .map(Some)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> serde_json::Result<S::Value> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error::custom("Value requested before key"))?;
+        seed.deserialize(self.parent.child(value)?)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct TableEnumAccess<'de> {
+    variant: &'de str,
+    value: TableDeserializer<'de>,
+}
+
+impl<'de> EnumAccess<'de> for TableEnumAccess<'de> {
+    type Error = serde_json::Error;
+    type Variant = TableDeserializer<'de>;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> serde_json::Result<(S::Value, Self::Variant)> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::new(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for TableDeserializer<'de> {
+    type Error = serde_json::Error;
+
+    fn unit_variant(self) -> serde_json::Result<()> {
+        IgnoredAny::deserialize(self).map(|_| ())
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> serde_json::Result<S::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> serde_json::Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> serde_json::Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+}
+
+impl<T: Serialize> Serialize for TurboStreamed<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let value = serde_json::to_value(&self.0)
+            .map_err(|e| ser::Error::custom(format_args!("Failed to convert to JSON: {e}")))?;
+        flatten(&value).serialize(serializer)
+    }
+}
+
+// Based on https://github.com/jacob-ebey/turbo-stream/blob/c974fa4af885aeb145e4517e474b6b4079677685/src/flatten.ts
+/// Flattens a value into the index table used by turbo-stream. Strings, numbers, and booleans that
+/// appear more than once share a single entry, and nulls are replaced with a sentinel index.
+pub fn flatten(value: &Value) -> Vec<Value> {
+    let mut flattener = Flattener::default();
+    flattener.insert(value);
+    flattener.table
+}
+
+#[derive(Hash, Eq, PartialEq)]
+enum Primitive {
+    Bool(bool),
+    Number(String),
+    String(String),
+}
+
+#[derive(Default)]
+struct Flattener {
+    table: Vec<Value>,
+    primitives: HashMap<Primitive, isize>,
+}
+
+impl Flattener {
+    fn index_of(&mut self, value: &Value) -> isize {
+        match value {
+            Value::Null => return NULL,
+            Value::Number(n) if n.as_f64().is_some_and(|x| x == 0.0 && x.is_sign_negative()) => {
+                return NEGATIVE_ZERO;
+            }
+            _ => {}
+        }
+        if let Some(primitive) = Self::primitive(value)
+            && let Some(&index) = self.primitives.get(&primitive)
+        {
+            return index;
+        }
+        self.insert(value)
+    }
+
+    fn insert(&mut self, value: &Value) -> isize {
+        let index = self.table.len() as isize;
+        self.table.push(Value::Null);
+        if let Some(primitive) = Self::primitive(value) {
+            self.primitives.insert(primitive, index);
+        }
+        let entry = match value {
+            Value::Array(values) => {
+                Value::Array(values.iter().map(|x| self.index_of(x).into()).collect())
+            }
+            Value::Object(values) => Value::Object(
+                values
+                    .iter()
+                    .map(|(k, v)| {
+                        let key = self.index_of(&Value::String(k.clone()));
+                        (format!("_{key}"), self.index_of(v).into())
+                    })
+                    .collect(),
+            ),
+            primitive => primitive.clone(),
+        };
+        self.table[index as usize] = entry;
+        index
+    }
+
+    fn primitive(value: &Value) -> Option<Primitive> {
+        match value {
+            Value::Bool(b) => Some(Primitive::Bool(*b)),
+            Value::Number(n) => Some(Primitive::Number(n.to_string())),
+            Value::String(s) => Some(Primitive::String(s.clone())),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sendou::mock_server::{MATCH_FIXTURE, TOURNAMENT_FIXTURE};
+    use crate::sendou::turbo_stream::{TurboStreamed, flatten, from_table, unflatten};
+    use proptest::prelude::*;
+    use serde::Deserialize;
+    use serde_json::{Value, json};
+    use std::collections::HashMap;
+
+    fn arbitrary_json() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::Bool),
+            any::<i64>().prop_map(Value::from),
+            (-1e9f64..1e9).prop_map(Value::from),
+            "[a-c_]{0,3}".prop_map(Value::String),
+        ];
+        leaf.prop_recursive(4, 64, 6, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..6).prop_map(Value::Array),
+                prop::collection::vec(("[a-c_0-9]{0,3}", inner), 0..6)
+                    .prop_map(|entries| Value::Object(entries.into_iter().collect())),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_test(value in arbitrary_json()) {
+            let encoded = serde_json::to_string(&TurboStreamed(&value)).unwrap();
+            let TurboStreamed(decoded) = serde_json::from_str::<TurboStreamed<Value>>(&encoded).unwrap();
+            // Parsing floats from text isn't exact, so compare against a plain JSON round trip
+            let expected = serde_json::from_str::<Value>(&value.to_string()).unwrap();
+            prop_assert_eq!(&decoded, &expected);
+            let table = serde_json::from_str::<Vec<Value>>(&encoded).unwrap();
+            prop_assert_eq!(unflatten(&table).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn flatten_test() {
+        let fixture = serde_json::from_slice::<Value>(MATCH_FIXTURE).unwrap();
+        let TurboStreamed(decoded) =
+            serde_json::from_slice::<TurboStreamed<Value>>(MATCH_FIXTURE).unwrap();
+        assert_eq!(Value::Array(flatten(&decoded)), fixture);
+
+        assert_eq!(
+            flatten(&json!({"a": null, "b": -0.0, "c": ["a", 1, 1, true]})),
+            [
+                json!({"_1": -5, "_2": -4, "_3": 4}),
+                json!("a"),
+                json!("b"),
+                json!("c"),
+                json!([1, 5, 5, 6]),
+                json!(1),
+                json!(true),
+            ]
+        );
+        assert_eq!(flatten(&Value::Null), [Value::Null]);
+    }
+
+    #[test]
+    fn typed_values_test() {
+        let table = json!([
+            {
+                "_1": 2, "_3": 4, "_5": 6, "_7": 8, "_9": 10, "_11": 12, "_13": 14, "_15": 16,
+                "_17": -2, "_18": 19, "_20": 23, "_24": 25,
+            },
+            "date", ["D", 1767225600000i64],
+            "big", ["B", "123456789012345678901234567890"],
+            "map", ["M", 1, 21, 22, 21],
+            "set", ["S", 21, 21],
+            "url", ["U", "https://sendou.ink/"],
+            "error", ["E", "Oops", "TypeError"],
+            "regex", ["R", "a+", "gi"],
+            "promise", ["P", 3],
+            "nan",
+            "holes", [-1, 21],
+            "small",
+            4,
+            5,
+            ["B", "42"],
+            "stringMap", ["M", 1, 21],
+        ]);
+        assert_eq!(
+            unflatten(table.as_array().unwrap()).unwrap(),
+            json!({
+                "date": "2026-01-01T00:00:00.000Z",
+                "big": "123456789012345678901234567890",
+                "map": [["date", 4], [5, 4]],
+                "set": [4, 4],
+                "url": "https://sendou.ink/",
+                "error": {"name": "TypeError", "message": "Oops"},
+                "regex": "/a+/gi",
+                "promise": null,
+                "nan": null,
+                "holes": [null, 4],
+                "small": 42,
+                "stringMap": {"date": 4},
+            })
+        );
+
+        assert_eq!(
+            from_table::<Value>(table.as_array().unwrap()).unwrap(),
+            unflatten(table.as_array().unwrap()).unwrap()
+        );
+
+        assert!(unflatten(json!([[0]]).as_array().unwrap()).is_err());
+        assert!(from_table::<Value>(json!([[0]]).as_array().unwrap()).is_err());
+        assert!(unflatten(json!([["Q"]]).as_array().unwrap()).is_err());
+        assert!(unflatten(json!([[-8]]).as_array().unwrap()).is_err());
+    }
+
+    #[test]
+    fn from_table_test() {
+        for fixture in [TOURNAMENT_FIXTURE, MATCH_FIXTURE] {
+            let table = serde_json::from_slice::<Vec<Value>>(fixture).unwrap();
+            assert_eq!(
+                from_table::<Value>(&table).unwrap(),
+                unflatten(&table).unwrap()
+            );
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Shape<'a> {
+            Empty,
+            Named(&'a str),
+        }
+        let table = json!([
+            {"_1": 2, "_3": 4, "_5": -7},
+            "empty", "Empty",
+            "named", {"_6": 7},
+            "missing",
+            "Named", "Zero",
+        ]);
+        assert_eq!(
+            from_table::<HashMap<&str, Option<Shape>>>(table.as_array().unwrap()).unwrap(),
+            HashMap::from([
+                ("empty", Some(Shape::Empty)),
+                ("named", Some(Shape::Named("Zero"))),
+                ("missing", None),
+            ])
+        );
     }
 }
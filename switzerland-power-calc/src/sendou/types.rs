@@ -1,4 +1,5 @@
 use crate::Result;
+use crate::sendou::polling::TournamentPoll;
 use crate::sendou::schema::{SendouId, Tournament, TournamentTeam};
 use serenity::all::ChannelId;
 use std::collections::HashMap;
@@ -8,4 +9,7 @@ pub type TeamsMap<'a> = HashMap<SendouId, &'a TournamentTeam>;
 pub trait GetTournamentFn: AsyncFn() -> Result<Tournament> {}
 impl<F> GetTournamentFn for F where F: AsyncFn() -> Result<Tournament> {}
 
+pub trait PollTournamentFn: AsyncFn() -> Result<TournamentPoll> {}
+impl<F> PollTournamentFn for F where F: AsyncFn() -> Result<TournamentPoll> {}
+
 pub type DiscordChannelsMap = HashMap<SendouId, ChannelId>;
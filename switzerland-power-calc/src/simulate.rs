@@ -0,0 +1,238 @@
+use crate::config::SeasonConfig;
+use crate::counts::leaderboard_count;
+use crate::db::{Database, SetResult, SwitzerlandPlayerMap, TournamentRecord};
+use crate::rating::rebuild_observed;
+use crate::{Result, print_player_simply};
+use ansi_term::Color;
+use skillratings::glicko2::expected_score;
+use std::path::{Path, PathBuf};
+
+/// How well the expected outcome of each set matched the actual results
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PredictionAccuracy {
+    pub sets: usize,
+    pub log_loss: f64,
+    pub brier_score: f64,
+}
+
+impl PredictionAccuracy {
+    fn add(&mut self, expected: f64, actual: f64) {
+        let expected = expected.clamp(1e-15, 1.0 - 1e-15);
+        self.sets += 1;
+        self.log_loss -= actual * expected.ln() + (1.0 - actual) * (1.0 - expected).ln();
+        self.brier_score += (expected - actual).powi(2);
+    }
+
+    fn finish(mut self) -> Self {
+        if self.sets > 0 {
+            self.log_loss /= self.sets as f64;
+            self.brier_score /= self.sets as f64;
+        }
+        self
+    }
+}
+
+/// How the ranks of a simulated database compare to the real one
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct RankChanges {
+    pub ranked: usize,
+    pub changed: usize,
+    pub total_difference: u32,
+}
+
+impl RankChanges {
+    fn between(real_players: &SwitzerlandPlayerMap, simulated: &Database) -> Self {
+        let mut changes = Self::default();
+        for player in &simulated.players {
+            let Some(rank) = player.rank else {
+                continue;
+            };
+            changes.ranked += 1;
+            if let Some(real_rank) = real_players.get(&player.id).and_then(|p| p.rank)
+                && real_rank != rank
+            {
+                changes.changed += 1;
+                changes.total_difference += real_rank.get().abs_diff(rank.get());
+            }
+        }
+        changes
+    }
+
+    fn mean_difference(&self) -> f64 {
+        if self.ranked > 0 {
+            self.total_difference as f64 / self.ranked as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Rebuilds the database with the given config, measuring how well each set was predicted
+pub fn simulate(
+    db: &Database,
+    config: &SeasonConfig,
+    tournaments: &[TournamentRecord],
+) -> (Database, PredictionAccuracy) {
+    let mut accuracy = PredictionAccuracy::default();
+    let players = rebuild_observed(config, tournaments, |set, rating1, rating2| {
+        let (expected, _) = expected_score(rating1, rating2);
+        let actual = match set.result {
+            SetResult::Win => 1.0,
+            SetResult::Loss => 0.0,
+        };
+        accuracy.add(expected, actual);
+    });
+    let mut new_db = db.with_players(players);
+    new_db.config = *config;
+    (new_db, accuracy.finish())
+}
+
+pub fn simulate_cli(db: &Path, matches_db: Option<&Path>, configs: &[PathBuf]) -> Result<()> {
+    let db = Database::read(db)?;
+    let tournaments = match matches_db {
+        Some(matches_db) => Database::read(matches_db)?.tournaments,
+        None => db.tournaments.clone(),
+    };
+    if tournaments.is_empty() {
+        println!("No recorded tournaments to simulate!");
+        return Ok(());
+    }
+    let real_players = db.clone().into_map();
+
+    let configs = [("Current config".to_string(), Ok(db.config))]
+        .into_iter()
+        .chain(
+            configs
+                .iter()
+                .map(|path| (path.display().to_string(), SeasonConfig::read(path))),
+        );
+    for (index, (name, config)) in configs.enumerate() {
+        if index > 0 {
+            println!();
+        }
+        println!("{}", Color::Green.paint(format!("# {name}")));
+        let (simulated, accuracy) = simulate(&db, &config?, &tournaments);
+
+        println!(
+            "Prediction accuracy over {} sets: log loss {:.4}, Brier score {:.4}",
+            accuracy.sets, accuracy.log_loss, accuracy.brier_score
+        );
+
+        let rank_changes = RankChanges::between(&real_players, &simulated);
+        println!(
+            "{} of {} ranked players changed rank compared to the real database (mean absolute difference {:.2})",
+            rank_changes.changed,
+            rank_changes.ranked,
+            rank_changes.mean_difference(),
+        );
+
+        println!(
+            "{}",
+            Color::Cyan.paint("Leaderboard (compared to the real database):")
+        );
+        for player in simulated
+            .players
+            .iter()
+            .filter(|p| p.show_rank())
+            .take(leaderboard_count(simulated.players.len()))
+        {
+            print_player_simply(real_players.get(&player.id), player, true, false);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{RatingMode, SeasonConfig};
+    use crate::db::{
+        Database, PlayerId, SetRecord, SetResult, TournamentParticipant, TournamentRecord,
+    };
+    use crate::simulate::{PredictionAccuracy, RankChanges, simulate};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn prediction_accuracy_test() {
+        let mut accuracy = PredictionAccuracy::default();
+        accuracy.add(0.8, 1.0);
+        accuracy.add(0.3, 0.0);
+        let accuracy = accuracy.finish();
+        assert_eq!(accuracy.sets, 2);
+        assert!((accuracy.log_loss - -(0.8f64.ln() + 0.7f64.ln()) / 2.0).abs() < 1e-12);
+        assert!((accuracy.brier_score - (0.04 + 0.09) / 2.0).abs() < 1e-12);
+
+        let mut certain = PredictionAccuracy::default();
+        certain.add(0.0, 1.0);
+        assert!(certain.finish().log_loss.is_finite());
+
+        let empty = PredictionAccuracy::default().finish();
+        assert_eq!((empty.log_loss, empty.brier_score), (0.0, 0.0));
+    }
+
+    #[test]
+    fn simulate_test() {
+        let participant = |id| TournamentParticipant {
+            player: PlayerId::Sendou(id),
+            display_name: None,
+            seeding_skill_ordinal: 0.0,
+        };
+        let set = |match_id, player1, player2, score: (u32, u32)| SetRecord {
+            match_id,
+            team1: vec![PlayerId::Sendou(player1)],
+            team2: vec![PlayerId::Sendou(player2)],
+            result: if score.0 > score.1 {
+                SetResult::Win
+            } else {
+                SetResult::Loss
+            },
+            score,
+        };
+        let tournament = |id, sets| TournamentRecord {
+            id,
+            date: Utc.with_ymd_and_hms(2026, id, 1, 0, 0, 0).unwrap(),
+            participants: (1..=4).map(participant).collect(),
+            sets,
+        };
+        let tournaments = [
+            tournament(
+                1,
+                vec![
+                    set(11, 1, 2, (3, 2)),
+                    set(12, 3, 4, (3, 0)),
+                    set(13, 1, 3, (3, 2)),
+                ],
+            ),
+            tournament(2, vec![set(21, 2, 3, (3, 0)), set(22, 4, 1, (3, 2))]),
+        ];
+        let set_config = SeasonConfig {
+            maximum_calced_rd: 350.0,
+            ..Default::default()
+        };
+        let maps_config = SeasonConfig {
+            rating_mode: RatingMode::Maps,
+            ..set_config
+        };
+
+        let (set_db, set_accuracy) = simulate(&Database::new(), &set_config, &tournaments);
+        let (maps_db, maps_accuracy) = simulate(&Database::new(), &maps_config, &tournaments);
+        assert_eq!(set_accuracy.sets, 5);
+        assert_eq!(maps_accuracy.sets, 5);
+        assert_ne!(set_accuracy.log_loss, maps_accuracy.log_loss);
+        assert_ne!(set_accuracy.brier_score, maps_accuracy.brier_score);
+        assert_eq!(maps_db.config.rating_mode, RatingMode::Maps);
+
+        let set_players = set_db.clone().into_map();
+        assert_eq!(
+            RankChanges::between(&set_players, &set_db),
+            RankChanges {
+                ranked: 4,
+                changed: 0,
+                total_difference: 0,
+            }
+        );
+        let rank_changes = RankChanges::between(&set_players, &maps_db);
+        assert_eq!(rank_changes.ranked, 4);
+        assert!(rank_changes.changed > 0);
+        assert!(rank_changes.mean_difference() > 0.0);
+    }
+}